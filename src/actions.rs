@@ -0,0 +1,71 @@
+use anyhow::{Context, Result, Ok};
+use std::{
+    env,
+    fs,
+    io::Write,
+};
+
+/// Whether donder-release is running inside GitHub Actions
+pub fn is_github_actions() -> bool {
+    env::var("GITHUB_ACTIONS").unwrap_or("".to_string()) == "true"
+}
+
+/// Returns the value of a GitHub Actions environment variable, empty when not running inside GitHub Actions.
+pub fn actions_var(name: &str) -> String {
+    match is_github_actions() {
+        true => env::var(name).unwrap_or("".to_string()),
+        false => "".to_string(),
+    }
+}
+
+/// Writes a step output to the file named by `$GITHUB_OUTPUT`, does nothing when it is not defined.
+///
+/// ## Arguments
+///
+/// * `key` - A string slice that holds the output name.
+/// * `value` - A string slice that holds the output value.
+pub fn write_output(key: &str, value: &str) -> Result<()> {
+    let output_path = env::var("GITHUB_OUTPUT").unwrap_or("".to_string());
+
+    if output_path.is_empty() {
+        return Ok(());
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&output_path)
+        .context(format!("failed to open {}", output_path))?;
+
+    writeln!(file, "{}={}", key, value)
+        .context(format!("failed to write to {}", output_path))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn actions_variables_are_only_read_inside_github_actions() {
+        let github_actions = env::var("GITHUB_ACTIONS").ok();
+        env::set_var("DONDER_RELEASE_TEST_REPOSITORY", "south-bay-warriors/donder-release");
+
+        env::set_var("GITHUB_ACTIONS", "true");
+        let inside = actions_var("DONDER_RELEASE_TEST_REPOSITORY");
+        let missing = actions_var("DONDER_RELEASE_TEST_MISSING");
+
+        env::remove_var("GITHUB_ACTIONS");
+        let outside = actions_var("DONDER_RELEASE_TEST_REPOSITORY");
+
+        if let Some(value) = github_actions {
+            env::set_var("GITHUB_ACTIONS", value);
+        }
+        env::remove_var("DONDER_RELEASE_TEST_REPOSITORY");
+
+        assert_eq!(inside, "south-bay-warriors/donder-release");
+        assert_eq!(missing, "");
+        assert_eq!(outside, "");
+    }
+}
@@ -1,17 +1,143 @@
 use anyhow::{Result, bail};
-use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, USER_AGENT};
+use async_trait::async_trait;
+use chrono::Local;
+use reqwest::{Method, RequestBuilder, Response, StatusCode, header::{AUTHORIZATION, CONTENT_TYPE, USER_AGENT, RETRY_AFTER}};
+use std::time::Duration;
 use serde::{Serialize, Deserialize};
 use semver::Version;
+use std::collections::HashMap;
 
-#[derive(Default, Debug)]
+use crate::actions::actions_var;
+
+/// Hosting service releases are published to
+#[async_trait]
+pub trait ReleaseProvider: std::fmt::Debug + Send + Sync {
+    /// Read-only request to validate connectivity and authentication
+    async fn check_repo_access(&self) -> Result<()>;
+
+    /// Read-only request to validate the token can push to the repository
+    async fn check_push_access(&self) -> Result<()>;
+
+    /// Publishes the release of a tag, as a pre release when its version has a pre release identifier. The release
+    /// with the given id is updated instead of creating a new one when it already exists.
+    async fn publish_release(
+        &self,
+        release_tag: &str,
+        tag_prefix: &str,
+        release_notes: &str,
+        target_commitish: &str,
+        existing_release: Option<&str>,
+    ) -> Result<()> {
+        let version = release_tag.strip_prefix(tag_prefix).unwrap_or(release_tag);
+        let prerelease = !Version::parse(version).unwrap().pre.is_empty();
+
+        match existing_release {
+            Some(release_id) => {
+                logInfo!("Release {} already exists, updating it", release_tag);
+                self.update_release(release_id, release_tag, release_notes, prerelease, target_commitish).await
+            },
+            None => self.create_release(release_tag, release_notes, prerelease, target_commitish).await,
+        }
+    }
+
+    /// Id of the release of a tag, none when the tag has no release
+    async fn find_release(&self, release_tag: &str) -> Result<Option<String>>;
+
+    /// Updates the release with the given id (see `find_release`) with the notes of its tag
+    async fn update_release(
+        &self,
+        release_id: &str,
+        release_tag: &str,
+        release_notes: &str,
+        prerelease: bool,
+        target_commitish: &str,
+    ) -> Result<()>;
+
+    /// Creates the release of a tag, attached to the target commit when it is not empty
+    async fn create_release(
+        &self,
+        release_tag: &str,
+        release_notes: &str,
+        prerelease: bool,
+        target_commitish: &str,
+    ) -> Result<()>;
+
+    /// Deletes the pre releases of the tags with the given prefix
+    async fn clean_pre_releases(&self, tag_prefix: &str) -> Result<()>;
+
+    /// Url of the release page of a tag
+    fn release_url(&self, origin_url: &str, release_tag: &str) -> String {
+        format!("{}/releases/tag/{}", origin_url, release_tag)
+    }
+}
+
+/// Longest time waited before retrying a request, whatever the Retry-After header asks for
+const MAX_RETRY_DELAY: u64 = 60;
+
+/// Sends requests again when they fail on the server side
+#[async_trait]
+pub trait SendWithRetry {
+    /// Sends the request retrying server errors (5xx) and rate limits (429) with exponential backoff, honoring the
+    /// Retry-After header. Timed out POST requests are not retried as they may have created the resource already.
+    /// Other responses are returned as is.
+    async fn send_with_retry(self, max_retries: u32) -> Result<Response>;
+}
+
+#[async_trait]
+impl SendWithRetry for RequestBuilder {
+    async fn send_with_retry(self, max_retries: u32) -> Result<Response> {
+        let mut attempt = 0;
+        let is_post = self.try_clone()
+            .and_then(|request| request.build().ok())
+            .map_or(false, |request| request.method() == Method::POST);
+
+        loop {
+            let request = match self.try_clone() {
+                Some(request) => request,
+                None => return Ok(self.send().await?),
+            };
+
+            let response = request.send().await;
+            let retryable = match &response {
+                Ok(response) => {
+                    response.status().is_server_error() || response.status() == StatusCode::TOO_MANY_REQUESTS
+                },
+                Err(e) => e.is_connect() || (e.is_timeout() && !is_post),
+            };
+
+            if !retryable || attempt >= max_retries {
+                return Ok(response?);
+            }
+
+            // Wait as long as the server asks, exponentially longer otherwise (1s, 2s, 4s...)
+            let delay = response.as_ref().ok()
+                .and_then(|response| response.headers().get(RETRY_AFTER))
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok())
+                .unwrap_or(2u64.pow(attempt))
+                .min(MAX_RETRY_DELAY);
+
+            attempt += 1;
+            logWarn!("Request failed, retrying in {}s ({}/{})", delay, attempt, max_retries);
+
+            tokio::time::sleep(Duration::from_secs(delay)).await;
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone)]
 pub struct GithubApi {
     /// The path to the git repository
     pub api_url: String,
+    /// The root of the API (e.g: https://api.github.com)
+    base_url: String,
 
     // to be used in request headers
     content_type: String,
     user_agent: String,
     authorization: String,
+    /// Number of times requests that failed on the server side (5xx or 429) are retried
+    max_retries: u32,
 }
 
 #[derive(Deserialize)]
@@ -21,23 +147,270 @@ pub struct Release {
     pub prerelease: bool,
 }
 
+#[derive(Deserialize)]
+pub struct PullRequest {
+    pub base: PullRequestRef,
+    pub head: PullRequestRef,
+}
+
+#[derive(Deserialize)]
+pub struct PullRequestRef {
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+    pub sha: String,
+}
+
+#[derive(Deserialize)]
+pub struct Deployment {
+    pub id: u64,
+}
+
+#[derive(Deserialize)]
+pub struct Gist {
+    pub html_url: String,
+}
+
+#[derive(Deserialize)]
+pub struct Repo {
+    pub permissions: Option<RepoPermissions>,
+}
+
+#[derive(Deserialize)]
+pub struct RepoPermissions {
+    #[serde(default)]
+    pub admin: bool,
+    #[serde(default)]
+    pub push: bool,
+}
+
 impl GithubApi {
-    pub fn new(token: &str, owner: &str, repo: &str) -> Self {
+    pub fn new(token: &str, owner: &str, repo: &str, api_url: &str, host: &str) -> Self {
+        // An explicit API URL takes precedence over the one GitHub Actions provides for the instance it runs on
+        let mut base_url = match api_url.is_empty() {
+            true => actions_var("GITHUB_API_URL"),
+            false => api_url.to_string(),
+        };
+
+        // GitHub Enterprise instances serve the API under the git host
+        if base_url.is_empty() {
+            base_url = match host.is_empty() || host == "github.com" {
+                true => "https://api.github.com".to_string(),
+                false => format!("https://{}/api/v3", host),
+            };
+        }
+
         Self {
-            api_url: format!("https://api.github.com/repos/{}/{}", owner, repo),
+            api_url: format!("{}/repos/{}/{}", base_url.trim_end_matches("/"), owner, repo),
+            base_url: base_url.trim_end_matches("/").to_string(),
             content_type: "application/vnd.github+json".to_string(),
             user_agent: "donder-release".to_string(),
-            authorization: format!("Bearer {}", token)
+            authorization: format!("Bearer {}", token),
+            max_retries: 0,
+        }
+    }
+
+    /// Retries requests that failed on the server side up to this number of times
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Replaces the authorization header of requests (e.g: token <token>)
+    pub fn with_authorization(mut self, authorization: String) -> Self {
+        self.authorization = authorization;
+        self
+    }
+
+    async fn get_repo(&self) -> Result<Repo> {
+        let client = reqwest::Client::new();
+        let response = client
+            .get(&self.api_url)
+            .header(CONTENT_TYPE, &self.content_type)
+            .header(USER_AGENT, &self.user_agent)
+            .header(AUTHORIZATION, &self.authorization)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            // get error message from response
+            let error_message = response.text().await?;
+            logError!("{}", error_message);
+            bail!(error_message);
+        }
+
+        Ok(response.json().await?)
+    }
+
+    async fn get_release_by_tag(&self, release_tag: &str) -> Result<Option<Release>> {
+        let client = reqwest::Client::new();
+        let response = client
+            .get(format!("{}/releases/tags/{}", &self.api_url, release_tag))
+            .header(CONTENT_TYPE, &self.content_type)
+            .header(USER_AGENT, &self.user_agent)
+            .header(AUTHORIZATION, &self.authorization)
+            .send_with_retry(self.max_retries)
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
         }
+
+        if !response.status().is_success() {
+            // get error message from response
+            let error_message = response.text().await?;
+            println!("error: {}", error_message);
+            bail!(error_message);
+        }
+
+        Ok(Some(response.json().await?))
     }
 
-    pub async fn publish_release(&self, release_tag: &str, tag_prefix: &str, release_notes: &str) -> Result<()> {
-        let version = release_tag.replace(tag_prefix, "");
+    pub async fn get_pull_request(&self, number: u64) -> Result<PullRequest> {
+        let client = reqwest::Client::new();
+        let response = client
+            .get(format!("{}/pulls/{}", &self.api_url, number))
+            .header(CONTENT_TYPE, &self.content_type)
+            .header(USER_AGENT, &self.user_agent)
+            .header(AUTHORIZATION, &self.authorization)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            // get error message from response
+            let error_message = response.text().await?;
+            logError!("{}", error_message);
+            bail!(error_message);
+        }
+
+        Ok(response.json().await?)
+    }
+
+    // mark a ref as deployed to an environment
+    pub async fn create_deployment(&self, release_tag: &str, environment: &str) -> Result<()> {
+        let request_body = PostDeployment {
+            git_ref: release_tag.to_string(),
+            environment: environment.to_string(),
+            auto_merge: false,
+            required_contexts: vec![],
+        };
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/deployments", &self.api_url))
+            .header(CONTENT_TYPE, &self.content_type)
+            .header(USER_AGENT, &self.user_agent)
+            .header(AUTHORIZATION, &self.authorization)
+            .json(&request_body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            // get error message from response
+            let error_message = response.text().await?;
+            println!("error: {}", error_message);
+            bail!(error_message);
+        }
+
+        let deployment: Deployment = response.json().await?;
+
+        let request_body = PostDeploymentStatus {
+            state: "success".to_string(),
+            environment: environment.to_string(),
+        };
+
+        let response = client
+            .post(format!("{}/deployments/{}/statuses", &self.api_url, deployment.id))
+            .header(CONTENT_TYPE, &self.content_type)
+            .header(USER_AGENT, &self.user_agent)
+            .header(AUTHORIZATION, &self.authorization)
+            .json(&request_body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            // get error message from response
+            let error_message = response.text().await?;
+            println!("error: {}", error_message);
+            bail!(error_message);
+        }
+
+        Ok(())
+    }
+
+    // publish release notes as a gist and return its url
+    pub async fn create_gist(&self, release_tag: &str, release_notes: &str) -> Result<String> {
+        let mut files = HashMap::new();
+        files.insert(format!("{}.md", release_tag), GistFile { content: release_notes.to_string() });
+
+        let request_body = PostGist {
+            description: format!("Release notes {}", release_tag),
+            public: false,
+            files,
+        };
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/gists", &self.base_url))
+            .header(CONTENT_TYPE, &self.content_type)
+            .header(USER_AGENT, &self.user_agent)
+            .header(AUTHORIZATION, &self.authorization)
+            .json(&request_body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            // get error message from response
+            let error_message = response.text().await?;
+            println!("error: {}", error_message);
+            bail!(error_message);
+        }
+
+        let gist: Gist = response.json().await?;
+
+        Ok(gist.html_url)
+    }
+}
+
+#[async_trait]
+impl ReleaseProvider for GithubApi {
+    async fn check_repo_access(&self) -> Result<()> {
+        self.get_repo().await?;
+
+        Ok(())
+    }
+
+    async fn check_push_access(&self) -> Result<()> {
+        let repo = self.get_repo().await?;
+
+        // Installation tokens (e.g: the Actions GITHUB_TOKEN) get no permissions in the response
+        match repo.permissions {
+            Some(permissions) if permissions.push || permissions.admin => Ok(()),
+            Some(_) => bail!("token does not have write access to the repository"),
+            None => {
+                logWarn!("Token permissions are not reported, skipping the write access check");
+                Ok(())
+            },
+        }
+    }
+
+    async fn create_release(
+        &self,
+        release_tag: &str,
+        release_notes: &str,
+        prerelease: bool,
+        target_commitish: &str,
+    ) -> Result<()> {
         let request_body = PostRelease {
             tag_name: release_tag.to_string(),
             name: release_tag.to_string(),
             body: release_notes.to_string(),
-            prerelease: !Version::parse(&version).unwrap().pre.is_empty(),
+            prerelease,
+            draft: false,
+            // GitHub uses the default branch otherwise, which may have moved past the tag
+            target_commitish: match target_commitish.is_empty() {
+                true => None,
+                false => Some(target_commitish.to_string()),
+            },
         };
 
         let client = reqwest::Client::new();
@@ -47,7 +420,7 @@ impl GithubApi {
             .header(USER_AGENT, &self.user_agent)
             .header(AUTHORIZATION, &self.authorization)
             .json(&request_body)
-            .send()
+            .send_with_retry(self.max_retries)
             .await?;
 
         if !response.status().is_success() {
@@ -60,14 +433,59 @@ impl GithubApi {
         Ok(())
     }
 
-    pub async fn clean_pre_releases(&self, tag_prefix: &str) -> Result<()> {
+    async fn find_release(&self, release_tag: &str) -> Result<Option<String>> {
+        Ok(self.get_release_by_tag(release_tag).await?.map(|release| release.id.to_string()))
+    }
+
+    // Releases of a deleted tag become drafts, they are published again once their tag is recreated
+    async fn update_release(
+        &self,
+        release_id: &str,
+        release_tag: &str,
+        release_notes: &str,
+        prerelease: bool,
+        target_commitish: &str,
+    ) -> Result<()> {
+        let request_body = PostRelease {
+            tag_name: release_tag.to_string(),
+            name: release_tag.to_string(),
+            body: release_notes.to_string(),
+            prerelease,
+            draft: false,
+            target_commitish: match target_commitish.is_empty() {
+                true => None,
+                false => Some(target_commitish.to_string()),
+            },
+        };
+
+        let client = reqwest::Client::new();
+        let response = client
+            .patch(format!("{}/releases/{}", &self.api_url, release_id))
+            .header(CONTENT_TYPE, &self.content_type)
+            .header(USER_AGENT, &self.user_agent)
+            .header(AUTHORIZATION, &self.authorization)
+            .json(&request_body)
+            .send_with_retry(self.max_retries)
+            .await?;
+
+        if !response.status().is_success() {
+            // get error message from response
+            let error_message = response.text().await?;
+            println!("error: {}", error_message);
+            bail!(error_message);
+        }
+
+        Ok(())
+    }
+
+    async fn clean_pre_releases(&self, tag_prefix: &str) -> Result<()> {
         let client = reqwest::Client::new();
         let response = client
             .get(format!("{}/releases", &self.api_url))
             .header(CONTENT_TYPE, &self.content_type)
             .header(USER_AGENT, &self.user_agent)
             .header(AUTHORIZATION, &self.authorization)
-            .send()
+            .send_with_retry(self.max_retries)
             .await?;
 
         if !response.status().is_success() {
@@ -81,15 +499,18 @@ impl GithubApi {
         let pre_releases: Vec<&Release> = releases.iter().filter(|r| r.prerelease).collect();
 
         for release in pre_releases {
-            let tag = release.tag_name.replace(tag_prefix, "");
-            let version = Version::parse(&tag).unwrap();
+            // Skip releases that belong to other tag prefixes (e.g: other packages)
+            let version = match release.tag_name.strip_prefix(tag_prefix).map(Version::parse) {
+                Some(Ok(version)) => version,
+                _ => continue,
+            };
             if !version.pre.is_empty() {
                 let response = client
                     .delete(format!("{}/releases/{}", &self.api_url, release.id))
                     .header(CONTENT_TYPE, &self.content_type)
                     .header(USER_AGENT, &self.user_agent)
                     .header(AUTHORIZATION, &self.authorization)
-                    .send()
+                    .send_with_retry(self.max_retries)
                     .await?;
 
                 if !response.status().is_success() {
@@ -111,4 +532,34 @@ struct PostRelease {
     name: String,
     body: String,
     prerelease: bool,
+    draft: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_commitish: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PostGist {
+    description: String,
+    public: bool,
+    files: HashMap<String, GistFile>,
+}
+
+#[derive(Serialize)]
+struct GistFile {
+    content: String,
+}
+
+#[derive(Serialize)]
+struct PostDeployment {
+    #[serde(rename = "ref")]
+    git_ref: String,
+    environment: String,
+    auto_merge: bool,
+    required_contexts: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct PostDeploymentStatus {
+    state: String,
+    environment: String,
 }
@@ -1,11 +1,18 @@
-use anyhow::{Context, Result, Ok};
+use anyhow::{Context, Result, Ok, bail};
 use regex::Captures;
 use std::{
     fs,
     path,
     io::{Read, Write, Seek, SeekFrom},
+    process::Command,
 };
 use serde_json::{Map, Value};
+use toml_edit::DocumentMut;
+use serde::Serialize;
+use semver::{Version, BuildMetadata};
+use chrono::Local;
+
+use crate::package::BumpFile;
 
 /// Extracts version data from a given text using a regular expression.
 ///
@@ -79,7 +86,219 @@ fn parse_path(path: &String, file: String) -> Result<String> {
     }
 }
 
-fn bump_file(version: &String, file_path: &String, build_metadata: &bool) -> Result<()> {
+/// Resolves the path of the versioning file a bump target writes to.
+///
+/// ## Arguments
+///
+/// * `target` - A string slice that holds the bump file target (cargo, npm, composer, pub, python, helm, android, ios, custom and regex).
+/// * `path` - A reference to a string representing the configured bump file path.
+pub fn target_file_path(target: &str, path: &String) -> Result<String> {
+    match target {
+        "cargo" => parse_path(path, "Cargo.toml".to_string()),
+        "npm" => parse_path(path, "package.json".to_string()),
+        "composer" => parse_path(path, "composer.json".to_string()),
+        "pub" => parse_path(path, "pubspec.yaml".to_string()),
+        "helm" => parse_path(path, "Chart.yaml".to_string()),
+        "python" if path.ends_with(".py") || path.ends_with(".toml") || path.ends_with(".cfg") => Ok(path.to_string()),
+        "python" => parse_path(path, "pyproject.toml".to_string()),
+        "android" => Ok(format!("{}/app/build.gradle", path.trim_end_matches("/"))),
+        "ios" => Ok(format!("{}.xcodeproj/project.pbxproj", path.trim_end_matches("/"))),
+        "custom" | "regex" => Ok(path.to_string()),
+        _ => bail!("invalid file bump target"),
+    }
+}
+
+/// Fails when writing `version` would not upgrade the version currently in a file.
+///
+/// Equal versions are only allowed when the build metadata is incremented, build metadata is otherwise ignored.
+///
+/// ## Arguments
+///
+/// * `version` - A string slice that holds the release version.
+/// * `caps` - A reference to the captures of the version currently in the file.
+/// * `build_metadata` - A boolean that indicates whether the build metadata is incremented.
+/// * `file_path` - A string slice that holds the path of the file, used in the error message.
+fn ensure_upgrade(version: &str, caps: &Captures, build_metadata: &bool, file_path: &str) -> Result<()> {
+    // Versions that are not semver (e.g: 1.2) are not compared
+    let (mut current, mut next) = match Version::parse(&caps[0]).ok().zip(Version::parse(version).ok()) {
+        Some(versions) => versions,
+        None => return Ok(()),
+    };
+
+    // Releases made only of build commits carry their own build metadata
+    let incrementing_build = *build_metadata || !next.build.is_empty();
+
+    current.build = BuildMetadata::EMPTY;
+    next.build = BuildMetadata::EMPTY;
+
+    if next < current || (next == current && !incrementing_build) {
+        bail!("{} already has version {}, refusing to write {}", file_path, &caps[0], version);
+    }
+
+    Ok(())
+}
+
+/// Computes the version to write to a versioning file.
+///
+/// When `build_metadata` is true the build number found in the file is incremented and appended to the
+/// version, replacing any build metadata the release version may have (e.g: releases made only of build
+/// commits). Otherwise the release version is written as is.
+///
+/// ## Arguments
+///
+/// * `version` - A string slice that holds the release version.
+/// * `caps` - The version data captured from the file (see `version_data`).
+/// * `build_metadata` - A boolean that indicates whether to include build metadata in the version.
+/// * `reset_build_metadata` - A boolean that indicates whether the build number restarts at 1 when the file
+///   version differs from the release version.
+fn final_version(version: &str, caps: &Captures, build_metadata: &bool, reset_build_metadata: &bool) -> String {
+    match build_metadata {
+        true => {
+            let version = version.split('+').next().unwrap();
+
+            // Version currently in the file without build metadata
+            let current_version = match caps.get(2) {
+                Some(pre) => format!("{}-{}", &caps[1], pre.as_str()),
+                None => caps[1].to_string(),
+            };
+
+            match caps.get(3) {
+                Some(_) if *reset_build_metadata && current_version != version => format!("{}+{}", version, 1),
+                Some(build) => format!("{}+{}", version, build.as_str().parse::<u32>().unwrap() + 1),
+                None => format!("{}+{}", version, 1),
+            }
+        },
+        false => version.to_string(),
+    }
+}
+
+/// Reads the version currently written in a bump file, `None` for targets whose version is not read.
+///
+/// ## Arguments
+///
+/// * `file` - A reference to the bump file.
+pub fn current_version(file: &BumpFile) -> Result<Option<String>> {
+    let file_path = target_file_path(&file.target, &file.path)?;
+
+    let text = match file.target.as_str() {
+        "cargo" => {
+            let doc = fs::read_to_string(&file_path)
+                .context(format!("failed to read file {}", file_path))?
+                .parse::<DocumentMut>()
+                .context(format!("failed to parse file {}", file_path))?;
+
+            cargo_version(&doc)
+                .context(format!("failed to find package version in file {}", file_path))?
+                .1
+        },
+        "pub" => fs::read_to_string(&file_path)
+            .context(format!("failed to read file {}", file_path))?,
+        "npm" | "composer" => read_json(&file_path)?["version"]
+            .as_str()
+            .context(format!("failed to find version in file {}", file_path))?
+            .to_string(),
+        "python" => {
+            let contents = fs::read_to_string(&file_path)
+                .context(format!("failed to read file {}", file_path))?;
+            let re = python_version_pattern(&file_path);
+            let mut section = String::new();
+
+            contents
+                .lines()
+                .find_map(|line| {
+                    if line.trim_start().starts_with('[') {
+                        section = line.trim().to_string();
+                    }
+
+                    match python_version_table(&file_path, &section) {
+                        true => re.captures(line).map(|caps| caps[3].to_string()),
+                        false => None,
+                    }
+                })
+                .context(format!("failed to find version in file {}", file_path))?
+        },
+        "helm" => {
+            let contents = fs::read_to_string(&file_path)
+                .context(format!("failed to read file {}", file_path))?;
+            let re = regex::Regex::new(r#"(?m)^version:\s*["']?([^"'\s#]+)"#).unwrap();
+
+            re.captures(&contents)
+                .context(format!("failed to find version in file {}", file_path))?[1]
+                .to_string()
+        },
+        "custom" | "regex" => {
+            let contents = fs::read_to_string(&file_path)
+                .context(format!("failed to read file {}", file_path))?;
+            let re = regex::Regex::new(&file.pattern)
+                .context(format!("invalid pattern for file {}", file_path))?;
+
+            re.captures(&contents)
+                .and_then(|caps| caps.get(1))
+                .context(format!("pattern matched nothing in file {}", file_path))?
+                .as_str()
+                .to_string()
+        },
+        _ => return Ok(None),
+    };
+
+    let caps = version_data(&text)
+        .context(format!("failed to find version in file {}", file_path))?;
+
+    Ok(Some(caps[0].to_string()))
+}
+
+/// Fails when the version of a bump file is not found, the lines android and ios targets write are checked since
+/// their version is not read.
+///
+/// ## Arguments
+///
+/// * `file` - A reference to the bump file.
+pub fn check_version(file: &BumpFile) -> Result<()> {
+    let patterns = match file.target.as_str() {
+        "android" => vec![("versionCode", r#"versionCode\s*=?\s*\d+"#), ("versionName", r#"versionName\s*=?\s*["']"#)],
+        "ios" => vec![
+            ("MARKETING_VERSION", r#"MARKETING_VERSION = .*;"#),
+            ("CURRENT_PROJECT_VERSION", r#"CURRENT_PROJECT_VERSION = .*;"#),
+        ],
+        _ => return current_version(file).map(|_| ()),
+    };
+
+    let file_path = target_file_path(&file.target, &file.path)?;
+    let contents = fs::read_to_string(&file_path)
+        .context(format!("failed to read file {}", file_path))?;
+
+    for (name, pattern) in patterns {
+        if !regex::Regex::new(pattern).unwrap().is_match(&contents) {
+            bail!("failed to find {} in file {}", name, file_path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Computes the version a bump file would be written with, without writing it.
+///
+/// Used to know the build metadata of a release before its files are bumped. Targets whose version is not read
+/// (e.g: android and ios) return the release version as is.
+///
+/// ## Arguments
+///
+/// * `file` - A reference to the bump file.
+/// * `version` - A string slice that holds the release version.
+/// * `reset_build_metadata` - A boolean that indicates whether the build number restarts when the version changes.
+pub fn bumped_version(file: &BumpFile, version: &str, reset_build_metadata: &bool) -> Result<String> {
+    let current_version = match current_version(file)? {
+        Some(current_version) => current_version,
+        None => return Ok(version.to_string()),
+    };
+
+    let caps = version_data(&current_version)
+        .context(format!("failed to find version in {}", file.path))?;
+
+    Ok(final_version(version, &caps, &file.build_metadata, reset_build_metadata))
+}
+
+fn bump_file(version: &str, file_path: &String, build_metadata: &bool, reset_build_metadata: &bool) -> Result<()> {
     let path = path::PathBuf::from(file_path);
 
     let mut file = fs::OpenOptions::new()
@@ -97,14 +316,10 @@ fn bump_file(version: &String, file_path: &String, build_metadata: &bool) -> Res
     let caps = version_data(&contents)
         .context(format!("failed to find version in file {}", file_path))?;
 
+    ensure_upgrade(version, &caps, build_metadata, file_path)?;
+
     // Final version with optional build metadata
-    let final_version = match build_metadata {
-        true => match caps.get(3) {
-            Some(build) => format!("{}+{}", version, build.as_str().parse::<u32>().unwrap() + 1),
-            None => format!("{}+{}", version, 1),
-        },
-        false => version.to_string(),
-    };
+    let final_version = final_version(version, &caps, build_metadata, reset_build_metadata);
         
     // Replace file version with the final version
     let new_contents = contents.replacen(&caps[0], &final_version, 1);
@@ -123,9 +338,233 @@ fn bump_file(version: &String, file_path: &String, build_metadata: &bool) -> Res
     Ok(())
 }
 
-pub fn bump_cargo(version: &String, file_path: &String, build_metadata: &bool) -> Result<()> {
+/// Refreshes Cargo.lock after a Cargo.toml bump so the workspace members hold their new versions.
+///
+/// ## Arguments
+///
+/// * `file_path` - A reference to a string that holds the path to the folder where to find Cargo.toml file.
+pub fn update_cargo_lock(file_path: &str) -> Result<()> {
+    let dir = file_path.replace("<root>", "");
+    let dir = match dir.is_empty() {
+        true => ".".to_string(),
+        false => dir,
+    };
+
+    let output = Command::new("cargo")
+        .args(["update", "--workspace", "--offline"])
+        .current_dir(&dir)
+        .output()
+        .context("failed to run cargo")?;
+
+    if !output.status.success() {
+        bail!("failed to update Cargo.lock: {}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    Ok(())
+}
+
+/// Reads the version of a Cargo.toml document, from `[package]` or else from `[workspace.package]`.
+///
+/// ## Returns
+///
+/// The table holding the version (package or workspace) and the version itself.
+fn cargo_version(doc: &DocumentMut) -> Option<(&'static str, String)> {
+    if let Some(version) = doc.get("package").and_then(|p| p.get("version")).and_then(|v| v.as_str()) {
+        return Some(("package", version.to_string()));
+    }
+
+    doc.get("workspace")
+        .and_then(|w| w.get("package"))
+        .and_then(|p| p.get("version"))
+        .and_then(|v| v.as_str())
+        .map(|version| ("workspace", version.to_string()))
+}
+
+/// Bumps the package version of a Cargo.toml file and writes the updated file back to disk.
+///
+/// The file is edited as a TOML document so only `package.version` (or `workspace.package.version`) changes,
+/// dependency versions, key order and comments are left untouched.
+///
+/// ## Arguments
+///
+/// * `version` - A string slice that holds the new version to be set.
+/// * `file_path` - A reference to a string that holds the path to the folder where to find Cargo.toml file.
+/// * `build_metadata` - A boolean that indicates whether to include build metadata in the version.
+/// * `reset_build_metadata` - A boolean that indicates whether the build number restarts when the version changes.
+pub fn bump_cargo(version: &str, file_path: &String, build_metadata: &bool, reset_build_metadata: &bool) -> Result<()> {
+    let p = parse_path(file_path, "Cargo.toml".to_string())?;
+
+    let contents = fs::read_to_string(&p)
+        .context(format!("failed to read file {}", p))?;
+    let mut doc = contents.parse::<DocumentMut>()
+        .context(format!("failed to parse file {}", p))?;
+
+    let (table, current_version) = cargo_version(&doc)
+        .context(format!("failed to find package version in file {}", p))?;
+
+    let caps = version_data(&current_version)
+        .context(format!("failed to find version in file {}", p))?;
+
+    ensure_upgrade(version, &caps, build_metadata, &p)?;
+
+    // Final version with optional build metadata
+    let final_version = final_version(version, &caps, build_metadata, reset_build_metadata);
+
+    let item = match table {
+        "package" => &mut doc["package"]["version"],
+        _ => &mut doc["workspace"]["package"]["version"],
+    };
+
+    // Keep the whitespace and comments around the value
+    if let Some(value) = item.as_value_mut() {
+        let decor = value.decor().clone();
+        *value = final_version.into();
+        *value.decor_mut() = decor;
+    }
+
+    fs::write(&p, doc.to_string())
+        .context(format!("failed to write to file {}", p))?;
+
+    Ok(())
+}
+
+/// Reads the package name of a npm or cargo versioning file.
+///
+/// ## Arguments
+///
+/// * `target` - A string slice that holds the bump file target.
+/// * `file_path` - A reference to a string that holds the path to the folder where to find the file.
+///
+/// ## Returns
+///
+/// The name of the package, none for other targets or when the file has no name.
+pub fn manifest_name(target: &str, file_path: &String) -> Result<Option<String>> {
+    match target {
+        "npm" => {
+            let p = parse_path(file_path, "package.json".to_string())?;
+            let package_json = read_json(&p)
+                .context(format!("failed to read file {}", p))?;
+
+            Ok(package_json.get("name").and_then(|n| n.as_str()).map(|n| n.to_string()))
+        },
+        "cargo" => {
+            let p = parse_path(file_path, "Cargo.toml".to_string())?;
+            let doc = fs::read_to_string(&p)
+                .context(format!("failed to read file {}", p))?
+                .parse::<DocumentMut>()
+                .context(format!("failed to parse file {}", p))?;
+
+            Ok(doc.get("package").and_then(|p| p.get("name")).and_then(|n| n.as_str()).map(|n| n.to_string()))
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Points a dependency requirement at a new version keeping its operator.
+///
+/// ## Returns
+///
+/// The new requirement, none when the requirement is not a plain version (e.g: workspace:*, file:../lib).
+fn dependency_requirement(requirement: &str, version: &str) -> Option<String> {
+    let re = regex::Regex::new(r"^([\^~=<>\s]*)\d+(\.\d+){0,2}([-+][0-9A-Za-z.\-+]*)?$").unwrap();
+
+    re.captures(requirement).map(|caps| format!("{}{}", &caps[1], version))
+}
+
+/// Updates the requirement of a dependency in the dependency sections of a package.json file.
+///
+/// ## Arguments
+///
+/// * `file_path` - A reference to a string that holds the path to the folder where to find package.json file.
+/// * `name` - A string slice that holds the npm name of the dependency.
+/// * `version` - A string slice that holds the released version of the dependency.
+pub fn bump_npm_dependency(file_path: &String, name: &str, version: &str) -> Result<()> {
+    let p = parse_path(file_path, "package.json".to_string())?;
+    let mut package_json = read_json(&p)
+        .context(format!("failed to read file {}", p))?;
+
+    let mut updated = false;
+
+    for section in ["dependencies", "devDependencies", "peerDependencies", "optionalDependencies"] {
+        let requirement = match package_json.get_mut(section).and_then(|d| d.get_mut(name)) {
+            Some(requirement) => requirement,
+            None => continue,
+        };
+
+        if let Some(new_requirement) = requirement.as_str().and_then(|r| dependency_requirement(r, version)) {
+            *requirement = Value::String(new_requirement);
+            updated = true;
+        }
+    }
+
+    if updated {
+        write_json(&p, &package_json)
+            .context(format!("failed to write to file {}", p))?;
+    }
+
+    Ok(())
+}
+
+/// Updates the requirement of a dependency in the dependency tables of a Cargo.toml file, keeping its formatting.
+///
+/// Both `lib = "1.2.3"` and `lib = { version = "1.2.3", path = "../lib" }` forms are updated, dependencies
+/// inherited from the workspace or without a version are left untouched.
+///
+/// ## Arguments
+///
+/// * `file_path` - A reference to a string that holds the path to the folder where to find Cargo.toml file.
+/// * `name` - A string slice that holds the crate name of the dependency.
+/// * `version` - A string slice that holds the released version of the dependency.
+pub fn bump_cargo_dependency(file_path: &String, name: &str, version: &str) -> Result<()> {
     let p = parse_path(file_path, "Cargo.toml".to_string())?;
-    bump_file(version, &p, build_metadata)
+
+    let contents = fs::read_to_string(&p)
+        .context(format!("failed to read file {}", p))?;
+    let mut doc = contents.parse::<DocumentMut>()
+        .context(format!("failed to parse file {}", p))?;
+
+    let mut updated = false;
+
+    for table in ["dependencies", "dev-dependencies", "build-dependencies"] {
+        if let Some(item) = doc.get_mut(table).and_then(|t| t.get_mut(name)) {
+            updated |= set_cargo_requirement(item, version);
+        }
+    }
+
+    if let Some(item) = doc.get_mut("workspace").and_then(|w| w.get_mut("dependencies")).and_then(|t| t.get_mut(name)) {
+        updated |= set_cargo_requirement(item, version);
+    }
+
+    if updated {
+        fs::write(&p, doc.to_string())
+            .context(format!("failed to write to file {}", p))?;
+    }
+
+    Ok(())
+}
+
+fn set_cargo_requirement(item: &mut toml_edit::Item, version: &str) -> bool {
+    let value = match item.is_str() {
+        true => item.as_value_mut(),
+        false => item.get_mut("version").and_then(|v| v.as_value_mut()),
+    };
+
+    let value = match value {
+        Some(value) => value,
+        None => return false,
+    };
+
+    let requirement = match value.as_str().and_then(|r| dependency_requirement(r, version)) {
+        Some(requirement) => requirement,
+        None => return false,
+    };
+
+    // Keep the whitespace and comments around the value
+    let decor = value.decor().clone();
+    *value = requirement.into();
+    *value.decor_mut() = decor;
+
+    true
 }
 
 fn read_json(file_path: &str) -> Result<Map<String, Value>> {
@@ -135,12 +574,60 @@ fn read_json(file_path: &str) -> Result<Map<String, Value>> {
 }
 
 fn write_json(file_path: &str, json: &Map<String, Value>) -> Result<()> {
-    let content = serde_json::to_string_pretty(json)?;
+    // Keep the indentation and trailing newline of the original file
+    let original = fs::read_to_string(file_path).unwrap_or_default();
+    let indent = original
+        .lines()
+        .skip(1)
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .find(|indent| !indent.is_empty())
+        .unwrap_or("  ");
+
+    let mut content = Vec::new();
+    let mut serializer = serde_json::Serializer::with_formatter(
+        &mut content,
+        serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes()),
+    );
+    json.serialize(&mut serializer)?;
+
+    if original.ends_with("\r\n") {
+        content.extend(b"\r\n");
+    } else if original.ends_with('\n') {
+        content.push(b'\n');
+    }
+
     let mut file = fs::File::create(file_path)?;
-    file.write_all(content.as_bytes())?;
+    file.write_all(&content)?;
     Ok(())
 }
 
+/// Writes a new top level version to a JSON file.
+///
+/// Only the `"version": "..."` text is replaced so the rest of the file stays byte identical, the document is
+/// reserialized when the version text cannot be found (e.g: escaped characters).
+fn write_json_version(
+    file_path: &str,
+    json: &mut Map<String, Value>,
+    current_version: &str,
+    final_version: &str,
+) -> Result<()> {
+    let contents = fs::read_to_string(file_path)
+        .context(format!("failed to read file {}", file_path))?;
+
+    let re = regex::Regex::new(&format!(r#""version"(\s*:\s*)"{}""#, regex::escape(current_version))).unwrap();
+
+    if re.is_match(&contents) {
+        let new_contents = re.replacen(&contents, 1, format!(r#""version"${{1}}"{}""#, final_version).as_str());
+
+        return fs::write(file_path, new_contents.as_bytes())
+            .context(format!("failed to write to file {}", file_path));
+    }
+
+    json["version"] = Value::String(final_version.to_string());
+
+    write_json(file_path, json)
+}
+
 /// Bumps the version of a package.json file and writes the updated file back to disk.
 ///
 /// ## Arguments
@@ -148,6 +635,7 @@ fn write_json(file_path: &str, json: &Map<String, Value>) -> Result<()> {
 /// * `version` - A string slice that holds the new version to be set.
 /// * `file_path` - A string slice that holds the path to the folder where to find package.json file.
 /// * `build_metadata` - A boolean that indicates whether to include build metadata in the version.
+/// * `reset_build_metadata` - A boolean that indicates whether the build number restarts when the version changes.
 ///
 /// ## Errors
 ///
@@ -164,44 +652,72 @@ fn write_json(file_path: &str, json: &Map<String, Value>) -> Result<()> {
 /// let file_path = "<root>".to_string();
 /// let build_metadata = true;
 ///
-/// match bump_npm(&version, &file_path, &build_metadata) {
+/// match bump_npm(&version, &file_path, &build_metadata, &false) {
 ///     Ok(_) => println!("Package version updated successfully!"),
 ///     Err(e) => println!("Error: {}", e),
 /// }
 /// ```
-pub fn bump_npm(version: &String, file_path: &String, build_metadata: &bool) -> Result<()> {
+pub fn bump_npm(version: &str, file_path: &String, build_metadata: &bool, reset_build_metadata: &bool) -> Result<()> {
     // Read the package.json file
     let p = parse_path(file_path, "package.json".to_string())?;
     let mut package_json = read_json(&p)?;
 
-    let pkg_version = package_json["version"].as_str().unwrap();
+    let pkg_version = package_json
+        .get("version")
+        .and_then(|v| v.as_str())
+        .context(format!("failed to find version in file {}", p))?
+        .to_string();
 
     // Capture metadata from version
-    let caps = version_data(pkg_version)
+    let caps = version_data(&pkg_version)
         .context(format!("failed to find metadata in version {}", file_path))?;
 
+    ensure_upgrade(version, &caps, build_metadata, &p)?;
+
     // Final version with optional build metadata
-    let final_version = match build_metadata {
-        true => match caps.get(3) {
-            Some(build) => format!("{}+{}", version, build.as_str().parse::<u32>().unwrap() + 1),
-            None => format!("{}+{}", version, 1),
-        },
-        false => version.to_string(),
-    };
+    let final_version = final_version(version, &caps, build_metadata, reset_build_metadata);
+
+    // Write the updated version back to the file keeping its formatting
+    write_json_version(&p, &mut package_json, &pkg_version, &final_version)
+}
+
+/// Bumps the version of a composer.json file and writes the updated file back to disk.
+///
+/// ## Arguments
+///
+/// * `version` - A string slice that holds the new version to be set.
+/// * `file_path` - A string slice that holds the path to the folder where to find composer.json file.
+/// * `build_metadata` - A boolean that indicates whether to include build metadata in the version.
+/// * `reset_build_metadata` - A boolean that indicates whether the build number restarts when the version changes.
+pub fn bump_composer(version: &str, file_path: &String, build_metadata: &bool, reset_build_metadata: &bool) -> Result<()> {
+    let p = parse_path(file_path, "composer.json".to_string())?;
+    let mut composer_json = read_json(&p)
+        .context(format!("failed to read file {}", p))?;
+
+    let current_version = composer_json
+        .get("version")
+        .and_then(|v| v.as_str())
+        .context(format!("failed to find version in file {}", p))?
+        .to_string();
+
+    // Capture metadata from version
+    let caps = version_data(&current_version)
+        .context(format!("failed to find metadata in version {}", file_path))?;
 
-    // Update the version field
-    package_json["version"] = serde_json::Value::String(final_version);
+    ensure_upgrade(version, &caps, build_metadata, &p)?;
 
-    // Write the updated package.json back to the file
-    write_json(&p, &package_json)
+    // Final version with optional build metadata
+    let final_version = final_version(version, &caps, build_metadata, reset_build_metadata);
+
+    write_json_version(&p, &mut composer_json, &current_version, &final_version)
 }
 
-pub fn bump_pub(version: &String, file_path: &String, build_metadata: &bool) -> Result<()> {
+pub fn bump_pub(version: &str, file_path: &String, build_metadata: &bool, reset_build_metadata: &bool) -> Result<()> {
     let p = parse_path(file_path, "pubspec.yaml".to_string())?;
-    bump_file(version, &p, build_metadata)
+    bump_file(version, &p, build_metadata, reset_build_metadata)
 }
 
-pub fn bump_android(version: &String, file_path: &String) -> Result<()> {
+pub fn bump_android(version: &String, file_path: &String, build_metadata: &bool) -> Result<()> {
     // Capture version data from version
     let caps = version_data(&version)
         .context(format!("failed to find metadata in version {}", file_path))?;
@@ -231,24 +747,32 @@ pub fn bump_android(version: &String, file_path: &String) -> Result<()> {
     build_gradle.read_to_string(&mut contents)
         .context(format!("failed to read file {}", p))?;
 
-    // Find the versionCode line
-    let re = regex::Regex::new(r#"versionCode \d+"#).unwrap();
+    // Find the versionCode line, the separator (spaces or =) is kept as is
+    let re = regex::Regex::new(r#"versionCode(\s*=?\s*)(\d+)"#).unwrap();
     let caps = re.captures(&contents)
         .context(format!("failed to find versionCode in file {}", p))?;
 
     // Increment the versionCode
-    let version_code = caps[0].trim_start_matches("versionCode ").trim_end().parse::<u32>().unwrap() + 1;
-    let new_contents = contents
-        .replace(&caps[0], &format!("versionCode {}", version_code));
+    let version_code = caps[2].parse::<u32>().unwrap() + 1;
+    let new_contents = re
+        .replace(&contents, format!("versionCode${{1}}{}", version_code).as_str())
+        .to_string();
 
-    // Find the versionName line
-    let re = regex::Regex::new(r#"versionName ".*""#).unwrap();
-    let caps = re.captures(&contents)
+    // Append the versionCode to the versionName as build metadata when enabled (e.g: 1.2.3+42)
+    let version_name = match build_metadata {
+        true => format!("{}+{}", version_name, version_code),
+        false => version_name,
+    };
+
+    // Find the versionName line, double or single quoted
+    let re = regex::Regex::new(r#"versionName(\s*=?\s*)(["'])[^"'\r\n]*["']"#).unwrap();
+    re.captures(&new_contents)
         .context(format!("failed to find versionName in file {}", p))?;
 
-    // Replace versionName with the new version
-    let new_contents = new_contents
-        .replace(&caps[0], &format!("versionName \"{}\"", version_name));
+    // Replace versionName with the new version keeping its quotes
+    let new_contents = re
+        .replace(&new_contents, format!("versionName${{1}}${{2}}{}${{2}}", version_name).as_str())
+        .to_string();
 
     // Erase contents of the file first to avoid issues with the new contents being shorter than the old contents
     build_gradle.set_len(0)
@@ -285,15 +809,20 @@ pub fn bump_ios(version: &String, file_path: &String) -> Result<()> {
     let next_project_version = match pre_release_version {
         "" => "5.0".to_string(),
         _ => {
-            let pre_release_components = pre_release_version.split(".").collect::<Vec<&str>>();
-            let next_project_version_id = match pre_release_components[0] {
+            // Split pre id from its counter, the separator between them is optional (e.g: rc.1, rc1)
+            let re = regex::Regex::new(r"^(.*?)[.\-]?(\d+)$").unwrap();
+            let (pre_id, pre_counter) = match re.captures(pre_release_version) {
+                Some(pre_caps) => (pre_caps[1].to_string(), pre_caps[2].to_string()),
+                None => (pre_release_version.to_string(), "0".to_string()),
+            };
+            let next_project_version_id = match pre_id.as_str() {
                 "alpha" => 1,
                 "beta" => 2,
                 "rc" => 3,
                 _ => 4,
             };
 
-            format!("{}.{}", next_project_version_id, pre_release_components[1])
+            format!("{}.{}", next_project_version_id, pre_counter)
         }
     };
 
@@ -339,4 +868,442 @@ pub fn bump_ios(version: &String, file_path: &String) -> Result<()> {
         .context(format!("failed to write to file {}", p))?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Bumps the version of a Helm chart and writes the updated Chart.yaml back to disk.
+///
+/// Only the top level `version` key is updated, and `appVersion` when `app_version` is true, so dependency
+/// versions are never touched.
+///
+/// ## Arguments
+///
+/// * `version` - A string slice that holds the new version to be set.
+/// * `file_path` - A reference to a string that holds the path to the folder where to find Chart.yaml.
+/// * `app_version` - A boolean that indicates whether appVersion is also updated.
+/// * `build_metadata` - A boolean that indicates whether to include build metadata in the version.
+/// * `reset_build_metadata` - A boolean that indicates whether the build number restarts when the version changes.
+pub fn bump_helm(
+    version: &str,
+    file_path: &String,
+    app_version: &bool,
+    build_metadata: &bool,
+    reset_build_metadata: &bool,
+) -> Result<()> {
+    let p = parse_path(file_path, "Chart.yaml".to_string())?;
+
+    let contents = fs::read_to_string(&p)
+        .context(format!("failed to read file {}", p))?;
+
+    // Top level keys only, dependencies are indented
+    let version_re = regex::Regex::new(r#"^(version:\s*)(["']?)([^"'\s#]+)(["']?)"#).unwrap();
+    let app_version_re = regex::Regex::new(r#"^(appVersion:\s*)(["']?)([^"'\s#]+)(["']?)"#).unwrap();
+
+    let mut found = false;
+    let mut new_contents = String::new();
+
+    for line in contents.split_inclusive('\n') {
+        if let Some(caps) = version_re.captures(line) {
+            let version_caps = version_data(&caps[3])
+                .context(format!("failed to find version in file {}", p))?;
+            ensure_upgrade(version, &version_caps, build_metadata, &p)?;
+            let final_version = final_version(version, &version_caps, build_metadata, reset_build_metadata);
+
+            new_contents.push_str(&version_re.replace(line, format!("${{1}}${{2}}{}${{4}}", final_version).as_str()));
+            found = true;
+        } else if *app_version && app_version_re.is_match(line) {
+            new_contents.push_str(&app_version_re.replace(line, format!("${{1}}${{2}}{}${{4}}", version).as_str()));
+        } else {
+            new_contents.push_str(line);
+        }
+    }
+
+    if !found {
+        bail!("failed to find version in file {}", p);
+    }
+
+    fs::write(&p, new_contents)
+        .context(format!("failed to write to file {}", p))?;
+
+    Ok(())
+}
+
+/// Regular expression of the version line of a python file, its third capture group matches the version.
+///
+/// `__version__` is used for .py files, setup.cfg versions are not quoted.
+///
+/// ## Arguments
+///
+/// * `file_path` - A string slice that holds the path to the file.
+fn python_version_pattern(file_path: &str) -> regex::Regex {
+    match path::Path::new(file_path).extension().and_then(|ext| ext.to_str()) {
+        Some("py") => regex::Regex::new(r#"^(\s*__version__\s*=\s*)(["'])([^"']*)(["'])"#).unwrap(),
+        Some("cfg") => regex::Regex::new(r#"^(\s*version\s*=\s*)()([^\s#;]+)()"#).unwrap(),
+        _ => regex::Regex::new(r#"^(\s*version\s*=\s*)(["'])([^"']*)(["'])"#).unwrap(),
+    }
+}
+
+/// Whether the version of a python file can be found in `section`, the last table header read.
+///
+/// `[project]` and `[tool.poetry]` hold the version of pyproject.toml and `[metadata]` the one of setup.cfg.
+///
+/// ## Arguments
+///
+/// * `file_path` - A string slice that holds the path to the file.
+/// * `section` - A string slice that holds the current table header (e.g: `[project]`).
+fn python_version_table(file_path: &str, section: &str) -> bool {
+    match path::Path::new(file_path).extension().and_then(|ext| ext.to_str()) {
+        Some("py") => true,
+        Some("cfg") => section == "[metadata]",
+        _ => section == "[project]" || section == "[tool.poetry]",
+    }
+}
+
+/// Bumps the version of a python project and writes the updated file back to disk.
+///
+/// For `.py` files the `__version__ = "..."` assignment is updated and for setup.cfg the `version` key of its
+/// `[metadata]` section. Otherwise the `version` key of the `[project]` or `[tool.poetry]` table of pyproject.toml is
+/// updated, leaving other version like keys (e.g: requires-python) untouched.
+///
+/// ## Arguments
+///
+/// * `version` - A string slice that holds the new version to be set.
+/// * `file_path` - A reference to a string that holds the path to the folder of pyproject.toml or to the file itself.
+/// * `build_metadata` - A boolean that indicates whether to include build metadata in the version.
+/// * `reset_build_metadata` - A boolean that indicates whether the build number restarts when the version changes.
+pub fn bump_python(version: &str, file_path: &String, build_metadata: &bool, reset_build_metadata: &bool) -> Result<()> {
+    let p = target_file_path("python", file_path)?;
+
+    let contents = fs::read_to_string(&p)
+        .context(format!("failed to read file {}", p))?;
+
+    let re = python_version_pattern(&p);
+    let mut section = String::new();
+    let mut found = false;
+    let mut new_contents = String::new();
+
+    for line in contents.split_inclusive('\n') {
+        // Track the current TOML table
+        if line.trim_start().starts_with('[') {
+            section = line.trim().to_string();
+        }
+
+        match re.captures(line) {
+            Some(caps) if !found && python_version_table(&p, &section) => {
+                let version_caps = version_data(&caps[3])
+                    .context(format!("failed to find version in file {}", p))?;
+                ensure_upgrade(version, &version_caps, build_metadata, &p)?;
+                let final_version = final_version(version, &version_caps, build_metadata, reset_build_metadata);
+
+                new_contents.push_str(&re.replace(line, format!("${{1}}${{2}}{}${{4}}", final_version).as_str()));
+                found = true;
+            },
+            _ => new_contents.push_str(line),
+        }
+    }
+
+    if !found {
+        bail!("failed to find version in file {}", p);
+    }
+
+    fs::write(&p, new_contents)
+        .context(format!("failed to write to file {}", p))?;
+
+    Ok(())
+}
+
+/// Bumps the version of any file using a user defined regular expression.
+///
+/// ## Arguments
+///
+/// * `version` - A string slice that holds the new version to be set.
+/// * `file_path` - A reference to a string that holds the path to the file.
+/// * `pattern` - A string slice that holds a regular expression whose first capture group matches the version.
+/// * `template` - A string slice that holds an optional replacement for the whole match, `%s` will be replaced
+///   with the version. When empty only the first capture group is replaced.
+/// * `build_metadata` - A boolean that indicates whether to include build metadata in the version.
+/// * `reset_build_metadata` - A boolean that indicates whether the build number restarts when the version changes.
+pub fn bump_custom(
+    version: &str,
+    file_path: &String,
+    pattern: &str,
+    template: &str,
+    build_metadata: &bool,
+    reset_build_metadata: &bool,
+) -> Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(file_path)
+        .context(format!("failed to open file {}", file_path))?;
+
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)
+        .context(format!("failed to read file {}", file_path))?;
+
+    let re = regex::Regex::new(pattern)
+        .context(format!("invalid pattern for file {}", file_path))?;
+    let caps = re.captures(&contents)
+        .context(format!("pattern matched nothing in file {}", file_path))?;
+    let whole_match = caps.get(0).unwrap();
+    let version_match = caps.get(1)
+        .context(format!("pattern has no version capture group for file {}", file_path))?;
+
+    // Capture version data from the matched version
+    let version_caps = version_data(version_match.as_str())
+        .context(format!("failed to find version in match of file {}", file_path))?;
+
+    ensure_upgrade(version, &version_caps, build_metadata, file_path)?;
+
+    // Final version with optional build metadata
+    let final_version = final_version(version, &version_caps, build_metadata, reset_build_metadata);
+
+    // Replace the whole match with the template or just the version capture group
+    let new_contents = match template.is_empty() {
+        true => format!(
+            "{}{}{}",
+            &contents[..version_match.start()],
+            final_version,
+            &contents[version_match.end()..],
+        ),
+        false => format!(
+            "{}{}{}",
+            &contents[..whole_match.start()],
+            template.replace("%s", &final_version),
+            &contents[whole_match.end()..],
+        ),
+    };
+
+    // Erase contents of the file first to avoid issues with the new contents being shorter than the old contents
+    file.set_len(0)
+        .context(format!("failed to erase contents of file {}", file_path))?;
+
+    // Write the new contents to the file
+    file.seek(SeekFrom::Start(0))
+        .context(format!("failed to seek to start of file {}", file_path))?;
+
+    file.write_all(new_contents.as_bytes())
+        .context(format!("failed to write to file {}", file_path))?;
+
+    Ok(())
+}
+
+/// Writes the current date to a key of the same file the version is bumped in.
+///
+/// For npm targets the top level JSON key is updated, for every other target a `key = "..."` or `key: ...` line
+/// is matched and its value replaced.
+///
+/// ## Arguments
+///
+/// * `target` - A string slice that holds the bump file target.
+/// * `file_path` - A reference to a string that holds the configured bump file path.
+/// * `date_key` - A string slice that holds the key to write the date to.
+/// * `date_format` - A string slice that holds the strftime format of the date (e.g: %Y-%m-%d).
+pub fn bump_date(target: &str, file_path: &String, date_key: &str, date_format: &str) -> Result<()> {
+    let p = target_file_path(target, file_path)?;
+    let date = Local::now().format(date_format).to_string();
+
+    if target == "npm" {
+        let mut json = read_json(&p)?;
+
+        json.insert(date_key.to_string(), Value::String(date));
+
+        return write_json(&p, &json);
+    }
+
+    let contents = fs::read_to_string(&p)
+        .context(format!("failed to read file {}", p))?;
+
+    // Match the key line keeping its indentation and assignment style
+    let re = regex::Regex::new(
+        &format!(r#"(?m)^(\s*{}\s*[=:]\s*)(["']?)[^"'\r\n]*(["']?)"#, regex::escape(date_key))
+    ).unwrap();
+    let caps = re.captures(&contents)
+        .context(format!("failed to find {} in file {}", date_key, p))?;
+
+    let new_contents = contents.replacen(
+        &caps[0],
+        &format!("{}{}{}{}", &caps[1], &caps[2], date, &caps[3]),
+        1,
+    );
+
+    fs::write(&p, new_contents)
+        .context(format!("failed to write to file {}", p))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn current_version_of_python_files() {
+        let dir = std::env::temp_dir().join(format!("donder-release-python-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let files = [
+            ("pyproject.toml", "[project]\nrequires-python = \">=3.8\"\nversion = \"1.2.3\"\n"),
+            ("setup.cfg", "[options]\nversion = 0.1.0\n\n[metadata]\nname = app\nversion = 1.2.3\n"),
+            ("__version__.py", "__version__ = '1.2.3'\n"),
+        ];
+
+        for (name, contents) in files {
+            let file_path = dir.join(name).to_string_lossy().to_string();
+            fs::write(&file_path, contents).unwrap();
+
+            let version = current_version(&BumpFile::new("python", &file_path)).unwrap();
+
+            assert_eq!(version, Some("1.2.3".to_string()), "{}", name);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dependency_requirement_keeps_its_operator() {
+        assert_eq!(dependency_requirement("^1.2.3", "1.3.0"), Some("^1.3.0".to_string()));
+        assert_eq!(dependency_requirement("~1.2", "1.3.0"), Some("~1.3.0".to_string()));
+        assert_eq!(dependency_requirement(">= 1.2.3-beta.1", "1.3.0"), Some(">= 1.3.0".to_string()));
+        assert_eq!(dependency_requirement("workspace:*", "1.3.0"), None);
+        assert_eq!(dependency_requirement("file:../lib", "1.3.0"), None);
+    }
+
+    #[test]
+    fn build_number_restarts_when_the_version_changes() {
+        let caps = version_data("1.2.3+5").unwrap();
+
+        assert_eq!(final_version("1.2.3", &caps, &true, &true), "1.2.3+6");
+        assert_eq!(final_version("1.3.0", &caps, &true, &true), "1.3.0+1");
+        assert_eq!(final_version("1.3.0", &caps, &true, &false), "1.3.0+6");
+        assert_eq!(final_version("1.3.0", &caps, &false, &true), "1.3.0");
+
+        let caps = version_data("1.3.0-beta.1+5").unwrap();
+
+        assert_eq!(final_version("1.3.0-beta.1", &caps, &true, &true), "1.3.0-beta.1+6");
+        assert_eq!(final_version("1.3.0-beta.2", &caps, &true, &true), "1.3.0-beta.2+1");
+    }
+
+    #[test]
+    fn release_date_is_written_to_the_date_key() {
+        let dir = std::env::temp_dir().join(format!("donder-release-date-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let folder = dir.to_string_lossy().to_string();
+        let version_file = dir.join("version.yaml").to_string_lossy().to_string();
+        fs::write(dir.join("package.json"), "{\n  \"name\": \"app\",\n  \"version\": \"1.2.3\"\n}\n").unwrap();
+        fs::write(&version_file, "version: 1.2.3\nreleased: \"2023-01-01\"\n").unwrap();
+
+        bump_date("npm", &folder, "releaseDate", "%Y-%m-%d").unwrap();
+        bump_date("regex", &version_file, "released", "%Y-%m-%d").unwrap();
+
+        let date = Local::now().format("%Y-%m-%d").to_string();
+        let package_json: Value = serde_json::from_str(&fs::read_to_string(dir.join("package.json")).unwrap()).unwrap();
+        let version_yaml = fs::read_to_string(&version_file).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(package_json["releaseDate"], Value::String(date.clone()));
+        assert_eq!(package_json["version"], "1.2.3");
+        assert_eq!(version_yaml, format!("version: 1.2.3\nreleased: \"{}\"\n", date));
+    }
+
+    #[test]
+    fn helm_chart_version_is_bumped() {
+        let dir = std::env::temp_dir().join(format!("donder-release-helm-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let folder = dir.to_string_lossy().to_string();
+        let chart = dir.join("Chart.yaml");
+        let contents = "apiVersion: v2\nname: app\nversion: 1.2.3\nappVersion: \"1.2.3\"\ndependencies:\n  - name: redis\n    version: 17.0.0\n";
+
+        fs::write(&chart, contents).unwrap();
+        bump_helm("1.3.0", &folder, &false, &false, &false).unwrap();
+        let without_app_version = fs::read_to_string(&chart).unwrap();
+
+        fs::write(&chart, contents).unwrap();
+        bump_helm("1.3.0", &folder, &true, &false, &false).unwrap();
+        let with_app_version = fs::read_to_string(&chart).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            without_app_version,
+            "apiVersion: v2\nname: app\nversion: 1.3.0\nappVersion: \"1.2.3\"\ndependencies:\n  - name: redis\n    version: 17.0.0\n",
+        );
+        assert_eq!(
+            with_app_version,
+            "apiVersion: v2\nname: app\nversion: 1.3.0\nappVersion: \"1.3.0\"\ndependencies:\n  - name: redis\n    version: 17.0.0\n",
+        );
+    }
+
+    #[test]
+    fn python_files_are_bumped() {
+        let dir = std::env::temp_dir().join(format!("donder-release-python-bump-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let files = [
+            (
+                "pyproject.toml",
+                "[project]\nname = \"app\"\nrequires-python = \">=3.8\"\nversion = \"1.2.3\"\n\n[tool.black]\nversion = \"23.1.0\"\n",
+                "[project]\nname = \"app\"\nrequires-python = \">=3.8\"\nversion = \"1.3.0\"\n\n[tool.black]\nversion = \"23.1.0\"\n",
+            ),
+            (
+                "setup.cfg",
+                "[options]\nversion = 0.1.0\n\n[metadata]\nname = app\nversion = 1.2.3\n",
+                "[options]\nversion = 0.1.0\n\n[metadata]\nname = app\nversion = 1.3.0\n",
+            ),
+            ("__version__.py", "__version__ = '1.2.3'\n", "__version__ = '1.3.0'\n"),
+        ];
+
+        for (name, contents, expected) in files {
+            let file_path = dir.join(name).to_string_lossy().to_string();
+            fs::write(&file_path, contents).unwrap();
+
+            bump_python("1.3.0", &file_path, &false, &false).unwrap();
+
+            assert_eq!(fs::read_to_string(&file_path).unwrap(), expected, "{}", name);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn custom_files_are_bumped_with_the_pattern() {
+        let dir = std::env::temp_dir().join(format!("donder-release-custom-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let file_path = dir.join("version.go").to_string_lossy().to_string();
+        let contents = "package app\n\nconst Version = \"1.2.3\"\n";
+
+        fs::write(&file_path, contents).unwrap();
+        bump_custom("1.3.0", &file_path, r#"Version = "(.*)""#, "", &false, &false).unwrap();
+        let captured = fs::read_to_string(&file_path).unwrap();
+
+        fs::write(&file_path, contents).unwrap();
+        bump_custom("1.3.0", &file_path, r#"const Version = "(.*)""#, "var Version = \"%s\"", &false, &false).unwrap();
+        let templated = fs::read_to_string(&file_path).unwrap();
+
+        fs::write(&file_path, contents).unwrap();
+        let error = bump_custom("1.3.0", &file_path, r#"Version = "\d+\.\d+\.\d+""#, "", &false, &false).unwrap_err();
+        let unchanged = fs::read_to_string(&file_path).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(captured, "package app\n\nconst Version = \"1.3.0\"\n");
+        assert_eq!(templated, "package app\n\nvar Version = \"1.3.0\"\n");
+        assert!(error.to_string().contains("pattern has no version capture group"), "{}", error);
+        assert_eq!(unchanged, contents);
+    }
+
+    #[test]
+    fn target_file_paths_are_resolved() {
+        let path = |target: &str, path: &str| target_file_path(target, &path.to_string()).unwrap();
+
+        assert_eq!(path("android", "android"), "android/app/build.gradle");
+        assert_eq!(path("ios", "ios/App"), "ios/App.xcodeproj/project.pbxproj");
+        assert_eq!(path("npm", "<root>"), "package.json");
+        assert_eq!(path("cargo", "crates/api"), "crates/api/Cargo.toml");
+        assert_eq!(path("python", "src/app/__version__.py"), "src/app/__version__.py");
+        assert_eq!(path("regex", "version.txt"), "version.txt");
+        assert!(target_file_path("maven", &"pom".to_string()).is_err());
+    }
+}
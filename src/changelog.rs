@@ -2,24 +2,170 @@ use crate::{
     git::Commit,
     ctx::{ReleaseType, ReleaseTypes},
 };
-use anyhow::{Result, Ok};
+use anyhow::{Context, Result, Ok};
 use regex::Regex;
-use chrono::Utc;
+use chrono::{Local, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{collections::HashMap, fs};
+
+/// Options that control how release notes are rendered
+#[derive(Debug, Deserialize)]
+pub struct NotesOptions {
+    /// Maximum number of characters of a commit description, 0 disables truncation
+    #[serde(default)]
+    pub max_desc_length: usize,
+    /// Append the number of commits to each section title
+    #[serde(default)]
+    pub section_counts: bool,
+    /// If not empty commits of unconfigured types are grouped under this section title
+    #[serde(default)]
+    pub unknown_type_section: String,
+    /// Commits with these scopes are grouped under the mapped section title regardless of their type
+    #[serde(default)]
+    pub scope_sections: HashMap<String, String>,
+    /// Character used for list bullets (- or *)
+    #[serde(default = "default_bullet_char")]
+    pub bullet_char: String,
+    /// Number of spaces used to indent commits nested under a scope
+    #[serde(default = "default_nested_indent")]
+    pub nested_indent: usize,
+    /// Link issue and pull request references (e.g: #123) of commit descriptions and footers
+    #[serde(default)]
+    pub link_references: bool,
+    /// Path of the origin url references link to (e.g: issues or pull)
+    #[serde(default = "default_references_path")]
+    pub references_path: String,
+    /// Credit the co-authors of a commit (Co-authored-by footers) after its description
+    #[serde(default)]
+    pub co_authors: bool,
+    /// If defined release notes are rendered with this handlebars template instead of the built-in layout
+    #[serde(default)]
+    pub changelog_template: String,
+    /// Format of the release date in release notes
+    #[serde(default = "default_changelog_date_format")]
+    pub changelog_date_format: String,
+    /// Timezone of the release date in release notes (utc or local)
+    #[serde(default = "default_changelog_timezone")]
+    pub changelog_timezone: String,
+}
+
+impl Default for NotesOptions {
+    fn default() -> Self {
+        Self {
+            max_desc_length: 0,
+            section_counts: false,
+            unknown_type_section: String::new(),
+            scope_sections: HashMap::new(),
+            bullet_char: default_bullet_char(),
+            nested_indent: default_nested_indent(),
+            link_references: false,
+            references_path: default_references_path(),
+            co_authors: false,
+            changelog_template: String::new(),
+            changelog_date_format: default_changelog_date_format(),
+            changelog_timezone: default_changelog_timezone(),
+        }
+    }
+}
+
+fn default_bullet_char() -> String {
+    "-".to_string()
+}
+
+fn default_nested_indent() -> usize {
+    2
+}
+
+fn default_references_path() -> String {
+    "issues".to_string()
+}
+
+fn default_changelog_date_format() -> String {
+    "%b %_d, %Y".to_string()
+}
+
+fn default_changelog_timezone() -> String {
+    "utc".to_string()
+}
 
 #[derive(Debug, Default)]
 pub struct Changelog {
     pub commits: Vec<ChangelogCommit>,
     pub next_release_version: String,
+    /// Build metadata shown in the header next to the version
+    pub build_metadata: String,
+    /// Authors contributing for the first time and the hash of their first commit
+    pub new_contributors: Vec<(String, String)>,
     pub notes: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Default, Clone)]
 pub struct ChangelogCommit {
     pub section_type: String,
     pub scope: String,
     pub desc: String,
     pub breaking: String,
     pub hash: String,
+    /// Name of the commit author
+    pub author: String,
+    /// Pre release the commit first appeared in
+    pub pre_release: String,
+    /// Footers of the commit body (e.g: ("Refs", "#12"))
+    pub footers: Vec<(String, String)>,
+}
+
+impl ChangelogCommit {
+    /// Conventional commit subject (e.g: feat(api): add endpoint)
+    pub fn subject(&self) -> String {
+        match self.scope.is_empty() {
+            true => format!("{}: {}", self.section_type, self.desc),
+            false => format!("{}({}): {}", self.section_type, self.scope, self.desc),
+        }
+    }
+
+    /// Description without the trailing reference of squash merges (e.g: "(#123)") and the issue numbers referenced
+    /// by it and the footers (e.g: "Closes #45")
+    pub fn references(&self) -> (String, Vec<String>) {
+        let mut references: Vec<String> = Vec::new();
+        let mut desc = self.desc.clone();
+
+        let re = Regex::new(r"\s*\(#(\d+)\)$").unwrap();
+        if let Some(caps) = re.captures(&self.desc) {
+            references.push(caps[1].to_string());
+            desc = re.replace(&self.desc, "").to_string();
+        }
+
+        let re = Regex::new(r"#(\d+)\b").unwrap();
+        for (key, value) in &self.footers {
+            if key.starts_with("BREAKING") {
+                continue;
+            }
+
+            for caps in re.captures_iter(value) {
+                if !references.contains(&caps[1].to_string()) {
+                    references.push(caps[1].to_string());
+                }
+            }
+        }
+
+        (desc, references)
+    }
+
+    /// Co-authors of the commit, as a GitHub mention when their email is a noreply address and by name otherwise
+    pub fn co_authors(&self) -> Vec<String> {
+        let re = Regex::new(r"^(.+?)\s*<(?:\d+\+)?([\w\-]+)@users\.noreply\.github\.com>$").unwrap();
+
+        self.footers
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case("Co-authored-by"))
+            .map(|(_, value)| match re.captures(value.trim()) {
+                Some(caps) => format!("@{}", &caps[2]),
+                None => value.split('<').next().unwrap_or(value).trim().to_string(),
+            })
+            .filter(|author| !author.is_empty())
+            .collect()
+    }
 }
 
 impl Changelog {
@@ -27,17 +173,41 @@ impl Changelog {
         Self {
             commits: Vec::new(),
             next_release_version: "0.0.0".to_string(),
+            build_metadata: "".to_string(),
+            new_contributors: Vec::new(),
             notes: "".to_string(),
         }
     }
 
-    pub fn parse_commit(&mut self, release_types: &Vec<String>, git_commit: &Commit) {
+    pub fn parse_commit(
+        &mut self,
+        release_types: &Vec<String>,
+        exclude_authors: &[String],
+        ignore_pattern: &str,
+        allow_empty_description: bool,
+        git_commit: &Commit,
+    ) {
+        // Ignore commits made by excluded authors (e.g: bots)
+        if exclude_authors.contains(&git_commit.author) {
+            return;
+        }
+
+        // Ignore commits marked to be left out of releases (e.g: [skip release])
+        if !ignore_pattern.is_empty()
+            && (git_commit.subject.contains(ignore_pattern) || git_commit.body.contains(ignore_pattern))
+        {
+            return;
+        }
+
         let mut commit = ChangelogCommit{
             section_type: String::new(),
             scope: String::new(),
             desc: String::new(),
             breaking: String::new(),
             hash: git_commit.hash.clone(),
+            author: git_commit.author.clone(),
+            pre_release: String::new(),
+            footers: parse_footers(&git_commit.body),
         };
 
         // save a reference to the first line to be used later if needed
@@ -62,45 +232,83 @@ impl Changelog {
                     Some(d) => commit.desc = d.as_str().to_string(),
                     None => (),
                 }
+                // The ! marker (e.g: feat!: or feat(api)!:) uses the description unless a footer describes it
+                if caps.get(3).is_some() {
+                    commit.breaking = commit.desc.clone();
+                }
             },
             None => (),
         }
 
-        // Parse commit body
-        for line in git_commit.body.lines() {
-            // Breaking changes
-            if line.starts_with("BREAKING CHANGE: ") {
-                commit.breaking = line.replace("BREAKING CHANGE: ", "").to_string();
-
-                // Get commit info if no section type is found, this can happen if the commit
-                // is not in the range of release_types but it's still relevant for the changelog
-                // because it contains a breaking change, which should trigger a major release.
-                if commit.section_type.is_empty() {
-                    let re = Regex::new(r"^(\w+)(\([\w\-\.]+\))?(!)?: (.+)").unwrap();
-                    let caps = re.captures(&git_commit.subject);
-                    match caps {
-                        Some(caps) => {
-                            commit.section_type = caps[1].to_string();
-                            match caps.get(2) {
-                                Some(s) => {
-                                    commit.scope = s.as_str()
-                                        .trim_matches(|c| c == '(' || c == ')')
-                                        .to_string();
-                                },
-                                None => (),
-                            }
-                            match caps.get(4) {
-                                Some(d) => commit.desc = d.as_str().to_string(),
-                                None => (),
-                            }
-                        },
-                        None => (),
-                    }
+        // A conventional subject without description (e.g: "fix:") uses the first body line or a placeholder
+        if commit.section_type.is_empty() && allow_empty_description {
+            let pattern = r"^(TOKENS){1}(\([\w\-\.]+\))?(!)?:\s*$".replace("TOKENS", release_types.join("|").as_str());
+            let re = Regex::new(&pattern).unwrap();
+
+            if let Some(caps) = re.captures(&git_commit.subject) {
+                commit.section_type = caps[1].to_string();
+                if let Some(s) = caps.get(2) {
+                    commit.scope = s.as_str().trim_matches(|c| c == '(' || c == ')').to_string();
+                }
+                commit.desc = git_commit.body
+                    .lines()
+                    .map(|line| line.trim())
+                    .find(|line| !line.is_empty())
+                    .unwrap_or("(no description)")
+                    .to_string();
+                if caps.get(3).is_some() {
+                    commit.breaking = commit.desc.clone();
                 }
             }
+        }
+
+        // Breaking changes
+        let breaking = commit.footers
+            .iter()
+            .find(|(key, _)| key == "BREAKING CHANGE" || key == "BREAKING-CHANGE")
+            .map(|(_, value)| value.replace('\n', " "));
 
-            // Footers
-            // TODO: Add support for multiple footers
+        if let Some(breaking) = breaking {
+            commit.breaking = breaking;
+
+            // Get commit info if no section type is found, this can happen if the commit
+            // is not in the range of release_types but it's still relevant for the changelog
+            // because it contains a breaking change, which should trigger a major release.
+            if commit.section_type.is_empty() {
+                let re = Regex::new(r"^(\w+)(\([\w\-\.]+\))?(!)?: (.+)").unwrap();
+                let caps = re.captures(&git_commit.subject);
+                match caps {
+                    Some(caps) => {
+                        commit.section_type = caps[1].to_string();
+                        match caps.get(2) {
+                            Some(s) => {
+                                commit.scope = s.as_str()
+                                    .trim_matches(|c| c == '(' || c == ')')
+                                    .to_string();
+                            },
+                            None => (),
+                        }
+                        match caps.get(4) {
+                            Some(d) => commit.desc = d.as_str().to_string(),
+                            None => (),
+                        }
+                    },
+                    None => (),
+                }
+            }
+        }
+
+        // Breaking changes marked with ! are released even if their type is not (e.g: refactor!:)
+        if commit.section_type.is_empty() {
+            let re = Regex::new(r"^(\w+)(\([\w\-\.]+\))?!: (.+)").unwrap();
+            if let Some(caps) = re.captures(&git_commit.subject) {
+                commit.section_type = caps[1].to_string();
+                if let Some(s) = caps.get(2) {
+                    commit.scope = s.as_str().trim_matches(|c| c == '(' || c == ')').to_string();
+                }
+                commit.desc = caps[3].to_string();
+                commit.breaking = commit.desc.clone();
+            }
         }
 
         // Ignore commits without section type
@@ -109,140 +317,564 @@ impl Changelog {
         }
     }
 
-    pub fn write_notes(&mut self, last_release_version: &String, release_types: &ReleaseTypes, origin_url: &str) -> Result<()> {
-        // Clean notes just in case
-        self.notes = String::new();
+    pub fn write_notes(
+        &mut self,
+        last_release_version: &String,
+        release_types: &ReleaseTypes,
+        origin_url: &str,
+        options: &NotesOptions,
+        include_authors: &bool,
+    ) -> Result<()> {
+        self.notes = self.render_notes(
+            |_| true,
+            last_release_version,
+            release_types,
+            origin_url,
+            options,
+            include_authors,
+        )?;
+
+        // Write new contributors section, templates render it themselves
+        if !self.new_contributors.is_empty() && options.changelog_template.is_empty() {
+            self.notes.push_str("\r\n### New Contributors\r\n");
+            for (author, hash) in &self.new_contributors {
+                self.notes.push_str(&format!(
+                    "{} {} made their first contribution in [{}]({}/commit/{})\r\n",
+                    options.bullet_char,
+                    escape_markdown(author),
+                    hash,
+                    &origin_url,
+                    hash,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Renders release notes for the commits accepted by `filter`
+    pub fn render_notes(
+        &self,
+        filter: impl Fn(&ChangelogCommit) -> bool,
+        last_release_version: &String,
+        release_types: &ReleaseTypes,
+        origin_url: &str,
+        options: &NotesOptions,
+        include_authors: &bool,
+    ) -> Result<String> {
+        let mut notes = String::new();
+        let commits = self.commits
+            .iter()
+            .filter(|c| filter(c))
+            .cloned()
+            .collect::<Vec<ChangelogCommit>>();
+
+        if !options.changelog_template.is_empty() {
+            return self.render_template(
+                &commits,
+                last_release_version,
+                release_types,
+                origin_url,
+                options,
+                include_authors,
+            );
+        }
+
+        // Version shown in the header, compare links always use the release tag
+        let header_version = match self.build_metadata.is_empty() {
+            true => self.next_release_version.clone(),
+            false => format!("{}+{}", self.next_release_version, self.build_metadata),
+        };
 
         // Write header
         if last_release_version.is_empty() {
-            self.notes.push_str(&format!("## {}\r\n\r\n", self.next_release_version));
+            notes.push_str(&format!("## {}\r\n\r\n", header_version));
         } else {
-            self.notes.push_str(&format!(
+            notes.push_str(&format!(
                 "## [{}]({}/compare/{}...{})\r\n\r\n",
-                self.next_release_version,
+                header_version,
                 &origin_url,
                 last_release_version,
                 self.next_release_version,
             ));
         }
-        self.notes.push_str(&format!("###### _{}_\r\n", Utc::now().format("%b %_d, %Y").to_string()));
-
-        // Group commits by section type in a tuple and push commits to a vector if section type already exists
-        let mut sections: Vec<(String, String, Vec<ChangelogCommit>)> = Vec::new();
-        for commit in &self.commits {
-            let mut found = false;
-
-            // Find section to push new commit
-            for (section_type, _, commits) in sections.iter_mut() {
-                if section_type == &commit.section_type {
-                    commits.push(commit.clone());
-                    found = true;
-                    break;
-                }
-            }
-
-            // Section not found so create a new one
-            if !found {
-                let section_type = commit.section_type.clone();
-                // Find section title from release_types section_type
-                let section_title = release_types
-                    .iter()
-                    .find(|r| r.commit_type == section_type)
-                    // If section_type is not found in release_types, use section_type as title
-                    // This can happen if the commit is not in the range of release_types but it's
-                    // still relevant for the changelog because it contains a breaking change, which
-                    // should trigger a major release.
-                    .unwrap_or(&ReleaseType {
-                        commit_type: section_type.clone(),
-                        bump: "".to_string(),
-                        section: section_type.clone(),
-                    })
-                    .section
-                    .clone();
-
-                // Create new section
-                sections.push((section_type, section_title, vec![commit.clone()]));
-            }
-        }
+        notes.push_str(&format!("###### _{}_\r\n", release_date(options)));
 
-        // Sort sections in the order of release_types
-        sections.sort_by(|a, b| {
-            release_types
-                .iter()
-                .position(|r| r.commit_type == a.0)
-                .cmp(&release_types.iter().position(|r| r.commit_type == b.0))
-        });
+        let sections = group_sections(&commits, release_types, options);
 
         // Write sections
         for (_, section_title, commits) in sections {
             // Write section title
-            self.notes.push_str(&format!("\r\n### {}\r\n", section_title));
-
-            // Group commits by scope
-            let mut scopes: Vec<(String, Vec<ChangelogCommit>)> = Vec::new();
-            for commit in commits {
-                let mut found = false;
-
-                // Find scope to push new commit
-                for (scope, commits) in scopes.iter_mut() {
-                    if scope == &commit.scope {
-                        commits.push(commit.clone());
-                        found = true;
-                        break;
-                    }
-                }
-
-                // Scope not found so create a new one
-                if !found {
-                    // Create new scope
-                    scopes.push((commit.scope.clone(), vec![commit.clone()]));
-                }
+            match options.section_counts {
+                true => notes.push_str(&format!("\r\n### {} ({})\r\n", section_title, commits.len())),
+                false => notes.push_str(&format!("\r\n### {}\r\n", section_title)),
             }
 
+            let scopes = group_scopes(commits);
+
             // Write section commits grouped by scope
             for (scope, commits) in scopes {
                 // Write scope
                 if !scope.is_empty() {
-                    self.notes.push_str(&format!("\r\n- **{}:**\r\n", scope));
+                    notes.push_str(&format!("\r\n{} **{}:**\r\n", options.bullet_char, escape_markdown(&scope)));
                 }
 
                 for commit in commits {
-                    // Write commit
-                    match scope.is_empty() {
-                        true => self.notes.push_str(&format!(
-                            "- {} ([{}]({}/commit/{}))\r\n",
-                            commit.desc,
-                            commit.hash,
-                            &origin_url,
-                            commit.hash,
-                        )),
-                        false => self.notes.push_str(&format!(
-                            "  - {} ([{}]({}/commit/{}))\r\n",
-                            commit.desc,
-                            commit.hash,
-                            &origin_url,
-                            commit.hash,
-                        )),
+                    let (desc, references) = match options.link_references {
+                        true => commit.references(),
+                        false => (commit.desc.clone(), Vec::new()),
+                    };
+
+                    let mut desc = escape_markdown(&truncate(&desc, options.max_desc_length));
+
+                    // Inline references of the description
+                    if options.link_references {
+                        desc = link_references(&desc, origin_url, &options.references_path);
                     }
+
+                    // Note the pre release that already shipped this commit
+                    if !commit.pre_release.is_empty() {
+                        desc.push_str(&format!(" _(first released in {})_", commit.pre_release));
+                    }
+
+                    // Trailing and footer references are linked at the end of the line
+                    // Dependency updates are not commits
+                    let mut links = match commit.hash.is_empty() {
+                        true => String::new(),
+                        false => format!(" ([{}]({}/commit/{}))", commit.hash, &origin_url, commit.hash),
+                    };
+
+                    if !references.is_empty() {
+                        let references = references
+                            .iter()
+                            .map(|r| format!("[#{}]({}/{}/{})", r, &origin_url, options.references_path, r))
+                            .collect::<Vec<String>>()
+                            .join(", ");
+
+                        links.push_str(&format!(" ({})", references));
+                    }
+
+                    // Credit the author of the commit
+                    if *include_authors && !commit.author.is_empty() {
+                        links.push_str(&format!(" by {}", commit.author));
+                    }
+
+                    // Credit pair programmed commits
+                    if options.co_authors {
+                        let co_authors = commit.co_authors();
+
+                        if !co_authors.is_empty() {
+                            links.push_str(&format!(" — with {}", co_authors.join(", ")));
+                        }
+                    }
+
+                    // Write commit
+                    let indent = match scope.is_empty() {
+                        true => String::new(),
+                        false => " ".repeat(options.nested_indent),
+                    };
+
+                    notes.push_str(&format!("{}{} {}{}\r\n", indent, options.bullet_char, desc, links));
                 }
             }
         }
 
         // filter commits with breaking changes
-        let breaking_changes: Vec<&ChangelogCommit> = self
-            .commits
+        let breaking_changes: Vec<&ChangelogCommit> = commits
             .iter()
             .filter(|c| !c.breaking.is_empty())
             .collect();
 
         // Write breaking changes section
         if !breaking_changes.is_empty() {
-            self.notes.push_str("\r\n### BREAKING CHANGES\r\n");
+            notes.push_str("\r\n### BREAKING CHANGES\r\n");
             for commit in breaking_changes {
-                self.notes.push_str(&format!("- {}\r\n", commit.breaking));
+                notes.push_str(&format!("{} {}\r\n", options.bullet_char, escape_markdown(&commit.breaking)));
             }
         }
 
-        Ok(())
+        Ok(notes)
+    }
+
+    /// Renders release notes of the given commits with the changelog template.
+    ///
+    /// The template receives the version, previous_version, date, compare_url, the sections with their commits
+    /// grouped by scope, the breaking_changes and the new_contributors of the release.
+    fn render_template(
+        &self,
+        commits: &[ChangelogCommit],
+        last_release_version: &String,
+        release_types: &ReleaseTypes,
+        origin_url: &str,
+        options: &NotesOptions,
+        include_authors: &bool,
+    ) -> Result<String> {
+        let template = fs::read_to_string(&options.changelog_template)
+            .context(format!("failed to read changelog template {}", options.changelog_template))?;
+
+        let data = self.notes_data(commits, last_release_version, release_types, origin_url, options, include_authors);
+
+        let mut handlebars = handlebars::Handlebars::new();
+        handlebars.register_escape_fn(handlebars::no_escape);
+
+        handlebars.render_template(&template, &data)
+            .context(format!("failed to render changelog template {}", options.changelog_template))
+    }
+
+    /// Release notes of all commits as structured JSON, with the same data changelog templates receive
+    pub fn to_json(
+        &self,
+        last_release_version: &String,
+        release_types: &ReleaseTypes,
+        origin_url: &str,
+        options: &NotesOptions,
+        include_authors: &bool,
+    ) -> Value {
+        self.notes_data(&self.commits, last_release_version, release_types, origin_url, options, include_authors)
+    }
+
+    /// Structured data of the release notes of the given commits
+    fn notes_data(
+        &self,
+        commits: &[ChangelogCommit],
+        last_release_version: &String,
+        release_types: &ReleaseTypes,
+        origin_url: &str,
+        options: &NotesOptions,
+        include_authors: &bool,
+    ) -> Value {
+        let commit_json = |commit: &ChangelogCommit| {
+            let (_, references) = commit.references();
+
+            json!({
+                "type": commit.section_type,
+                "scope": commit.scope,
+                "description": commit.desc,
+                "subject": commit.subject(),
+                "breaking": commit.breaking,
+                "hash": commit.hash,
+                "url": match commit.hash.is_empty() {
+                    true => String::new(),
+                    false => format!("{}/commit/{}", origin_url, commit.hash),
+                },
+                "author": match include_authors {
+                    true => commit.author.clone(),
+                    false => String::new(),
+                },
+                "co_authors": commit.co_authors(),
+                "references": references,
+                "footers": commit.footers
+                    .iter()
+                    .map(|(key, value)| json!({ "key": key, "value": value }))
+                    .collect::<Vec<Value>>(),
+                "pre_release": commit.pre_release,
+            })
+        };
+
+        let sections = group_sections(commits, release_types, options)
+            .into_iter()
+            .map(|(section_type, title, commits)| json!({
+                "type": section_type,
+                "title": title,
+                "count": commits.len(),
+                "commits": commits.iter().map(commit_json).collect::<Vec<Value>>(),
+                "scopes": group_scopes(commits)
+                    .iter()
+                    .map(|(scope, commits)| json!({
+                        "scope": scope,
+                        "commits": commits.iter().map(commit_json).collect::<Vec<Value>>(),
+                    }))
+                    .collect::<Vec<Value>>(),
+            }))
+            .collect::<Vec<Value>>();
+
+        let compare_url = match last_release_version.is_empty() {
+            true => String::new(),
+            false => format!("{}/compare/{}...{}", origin_url, last_release_version, self.next_release_version),
+        };
+
+        json!({
+            "version": self.next_release_version,
+            "build_metadata": self.build_metadata,
+            "previous_version": last_release_version,
+            "date": release_date(options),
+            "compare_url": compare_url,
+            "origin_url": origin_url,
+            "sections": sections,
+            "breaking_changes": commits
+                .iter()
+                .filter(|c| !c.breaking.is_empty())
+                .map(commit_json)
+                .collect::<Vec<Value>>(),
+            "new_contributors": self.new_contributors
+                .iter()
+                .map(|(author, hash)| json!({ "author": author, "hash": hash }))
+                .collect::<Vec<Value>>(),
+        })
+    }
+}
+
+/// Formats the current date with the configured date format and timezone
+fn release_date(options: &NotesOptions) -> String {
+    match options.changelog_timezone.as_str() {
+        "local" => Local::now().format(&options.changelog_date_format).to_string(),
+        _ => Utc::now().format(&options.changelog_date_format).to_string(),
+    }
+}
+
+/// Groups commits in sections by type (or mapped scope) in the order of release_types, with their titles
+fn group_sections(
+    commits: &[ChangelogCommit],
+    release_types: &ReleaseTypes,
+    options: &NotesOptions,
+) -> Vec<(String, String, Vec<ChangelogCommit>)> {
+    // Group commits by section type in a tuple and push commits to a vector if section type already exists
+    let mut sections: Vec<(String, String, Vec<ChangelogCommit>)> = Vec::new();
+    for commit in commits {
+        let mut found = false;
+
+        // Commits with a mapped scope are elevated to their scope section, taking precedence over the type
+        // section. Otherwise commits of unconfigured types can be rolled into a single section.
+        let commit_section_type = match options.scope_sections.get(&commit.scope) {
+            Some(scope_section) => scope_section.clone(),
+            None => match options.unknown_type_section.is_empty()
+                || release_types.iter().any(|r| r.commit_type == commit.section_type)
+            {
+                true => commit.section_type.clone(),
+                false => options.unknown_type_section.clone(),
+            },
+        };
+
+        // Find section to push new commit
+        for (section_type, _, commits) in sections.iter_mut() {
+            if section_type == &commit_section_type {
+                commits.push(commit.clone());
+                found = true;
+                break;
+            }
+        }
+
+        // Section not found so create a new one
+        if !found {
+            let section_type = commit_section_type;
+            // If section_type is not found in release_types, use section_type as title
+            // This can happen if the commit is not in the range of release_types but it's
+            // still relevant for the changelog because it contains a breaking change, which
+            // should trigger a major release.
+            let unknown_type = ReleaseType {
+                commit_type: section_type.clone(),
+                bump: "".to_string(),
+                section: section_type.clone(),
+                ..Default::default()
+            };
+
+            // Find section title from release_types section_type
+            let release_type = release_types
+                .iter()
+                .find(|r| r.commit_type == section_type)
+                .unwrap_or(&unknown_type);
+
+            // Prefix the title with the emoji of the type (e.g: ✨ Features)
+            let section_title = match release_type.emoji.is_empty() {
+                true => release_type.section.clone(),
+                false => format!("{} {}", release_type.emoji, release_type.section),
+            };
+
+            // Create new section
+            sections.push((section_type, section_title, vec![commit.clone()]));
+        }
+    }
+
+    // Sort sections in the order of release_types
+    sections.sort_by(|a, b| {
+        release_types
+            .iter()
+            .position(|r| r.commit_type == a.0)
+            .cmp(&release_types.iter().position(|r| r.commit_type == b.0))
+    });
+
+    sections
+}
+
+/// Groups commits of a section by scope, keeping the order of the first commit of each scope
+fn group_scopes(commits: Vec<ChangelogCommit>) -> Vec<(String, Vec<ChangelogCommit>)> {
+    // Group commits by scope
+    let mut scopes: Vec<(String, Vec<ChangelogCommit>)> = Vec::new();
+    for commit in commits {
+        let mut found = false;
+
+        // Find scope to push new commit
+        for (scope, commits) in scopes.iter_mut() {
+            if scope == &commit.scope {
+                commits.push(commit.clone());
+                found = true;
+                break;
+            }
+        }
+
+        // Scope not found so create a new one
+        if !found {
+            // Create new scope
+            scopes.push((commit.scope.clone(), vec![commit.clone()]));
+        }
     }
-}
\ No newline at end of file
+
+    scopes
+}
+
+/// Converts markdown release notes to HTML.
+pub fn notes_html(markdown: &str) -> String {
+    let mut html = String::new();
+
+    pulldown_cmark::html::push_html(&mut html, pulldown_cmark::Parser::new(markdown));
+
+    html
+}
+
+/// Truncates a text to a maximum number of characters, appending an ellipsis when truncated.
+///
+/// Characters are counted as unicode scalar values so multi-byte characters are never split.
+fn truncate(text: &str, max_length: usize) -> String {
+    if max_length == 0 || text.chars().count() <= max_length {
+        return text.to_string();
+    }
+
+    format!("{}…", text.chars().take(max_length).collect::<String>())
+}
+
+/// Escapes the characters of a text that markdown would otherwise render as formatting or html.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '~') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Links the issue and pull request references of a text (e.g: #123) to the origin url.
+fn link_references(text: &str, origin_url: &str, references_path: &str) -> String {
+    let re = Regex::new(r"(^|[^\w\[/])#(\d+)\b").unwrap();
+
+    re.replace_all(text, |caps: &regex::Captures| {
+        format!("{}[#{}]({}/{}/{})", &caps[1], &caps[2], origin_url, references_path, &caps[2])
+    }).to_string()
+}
+
+/// Parses the footers of a commit body following the conventional commits grammar.
+///
+/// A footer is a `key: value` or `key #value` line, where the key is a word token or `BREAKING CHANGE`. Footers are
+/// only read from the last paragraph of the body, which must start with one, and their values continue on the
+/// following lines until the next footer. `BREAKING CHANGE:` lines are also recognized at the start of any other
+/// line of the body.
+///
+/// ## Arguments
+///
+/// * `body` - A string slice that holds the commit body.
+pub fn parse_footers(body: &str) -> Vec<(String, String)> {
+    let re = Regex::new(r"^(BREAKING CHANGE|[\w\-]+)(: | #)(.*)$").unwrap();
+    let mut footers: Vec<(String, String)> = Vec::new();
+
+    // Last paragraph of the body
+    let lines = body.trim_end().lines().collect::<Vec<&str>>();
+    let start = lines.iter().rposition(|line| line.trim().is_empty()).map_or(0, |i| i + 1);
+
+    for line in &lines[start..] {
+        match re.captures(line) {
+            Some(caps) => {
+                let value = match &caps[2] {
+                    " #" => format!("#{}", &caps[3]),
+                    _ => caps[3].to_string(),
+                };
+
+                footers.push((caps[1].to_string(), value));
+            },
+            None => match footers.last_mut() {
+                // Values may span multiple lines
+                Some((_, value)) => {
+                    value.push('\n');
+                    value.push_str(line);
+                },
+                // A paragraph that does not start with a footer is not a footer paragraph
+                None => break,
+            },
+        }
+    }
+
+    // Breaking changes written inside the body paragraphs
+    let breaking_re = Regex::new(r"^(BREAKING[ -]CHANGE): (.*)$").unwrap();
+    let end = match footers.is_empty() {
+        true => lines.len(),
+        false => start,
+    };
+
+    let breaking = lines[..end]
+        .iter()
+        .filter_map(|line| breaking_re.captures(line))
+        .map(|caps| (caps[1].to_string(), caps[2].to_string()))
+        .collect::<Vec<(String, String)>>();
+
+    footers.splice(0..0, breaking);
+
+    footers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn footers_are_read_from_the_last_paragraph() {
+        let body = "Fixes: the first paragraph is not a footer\n\nRefs: #12\nBREAKING CHANGE: configs are read\nfrom toml";
+
+        assert_eq!(parse_footers(body), vec![
+            ("Refs".to_string(), "#12".to_string()),
+            ("BREAKING CHANGE".to_string(), "configs are read\nfrom toml".to_string()),
+        ]);
+        assert!(parse_footers("Explains the change.\nRefs: #12").is_empty());
+    }
+
+    #[test]
+    fn breaking_change_is_read_inside_a_paragraph() {
+        let body = "Configs are read from toml.\nBREAKING CHANGE: yaml configs are no longer read\n\nRefs: #12";
+
+        assert_eq!(parse_footers(body), vec![
+            ("BREAKING CHANGE".to_string(), "yaml configs are no longer read".to_string()),
+            ("Refs".to_string(), "#12".to_string()),
+        ]);
+        assert_eq!(parse_footers("Explains the change.\nBREAKING-CHANGE: drop node 14"), vec![
+            ("BREAKING-CHANGE".to_string(), "drop node 14".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn markdown_characters_are_escaped() {
+        assert_eq!(escape_markdown("support __proto__ and <T>"), r"support \_\_proto\_\_ and \<T\>");
+        assert_eq!(escape_markdown("read [config](url) with `--path`"), r"read \[config\](url) with \`--path\`");
+        assert_eq!(escape_markdown("add endpoint"), "add endpoint");
+    }
+
+    #[test]
+    fn notes_are_converted_to_html() {
+        assert_eq!(
+            notes_html("### Features\r\n- add endpoint ([abc1234](https://github.com/o/r/commit/abc1234))\r\n"),
+            "<h3>Features</h3>\n<ul>\n<li>add endpoint (<a href=\"https://github.com/o/r/commit/abc1234\">abc1234</a>)</li>\n</ul>\n",
+        );
+    }
+
+    #[test]
+    fn references_are_linked_to_the_origin() {
+        let origin_url = "https://github.com/o/r";
+
+        assert_eq!(link_references("fix #12", origin_url, "issues"), "fix [#12](https://github.com/o/r/issues/12)");
+        assert_eq!(
+            link_references("#3 and #4", origin_url, "pull"),
+            "[#3](https://github.com/o/r/pull/3) and [#4](https://github.com/o/r/pull/4)",
+        );
+        assert_eq!(link_references("see o/r#12 and [#5](url)", origin_url, "issues"), "see o/r#12 and [#5](url)");
+    }
+}
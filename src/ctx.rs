@@ -5,51 +5,265 @@ use std::{
 };
 use anyhow::{Context, Result, bail, Ok};
 use serde::Deserialize;
+use semver::{Prerelease, Version};
+use regex::Regex;
+use chrono::{Local, format::{Item, StrftimeItems}};
 
 use crate::{
     git::Git,
-    api::GithubApi,
-    package::{Pkg, BumpFiles}
+    api::{GithubApi, ReleaseProvider},
+    gitlab::GitLabApi,
+    gitea::GiteaApi,
+    package::{Pkg, BumpFile, BumpFiles},
+    changelog::NotesOptions,
+    manifest::read_manifest,
+    actions::actions_var,
 };
 
 /// Initializes the configuration file
 pub fn init_config() -> Result<()> {
     let config = r#"# Configuration file for donder-release
+#
+# When running inside GitHub Actions (GITHUB_ACTIONS=true) the following defaults are inferred:
+# - GITHUB_TOKEN is used when GH_TOKEN is not defined
+# - GITHUB_SERVER_URL and GITHUB_REPOSITORY are used when there is no origin remote
+# - GITHUB_API_URL is used as the API URL
+#
+# Whenever GITHUB_OUTPUT is defined the next_version, tag and released step outputs are written to it (prefixed with
+# <package>_ for packages)
 
 # Release message of the release commit - /%s/ will be replaced with the release version
 release_message: "chore(release): %s"
 # Prefix of the release tag
 tag_prefix: v
+# Release message and tag prefix overrides of packages keyed by package name (root for the root package), the global
+# release_message and tag_prefix are used for the rest. Package tags are still prefixed with the package name.
+# Commits of a package are those changing files under its path, list glob paths (relative to the repo root) to
+# include instead and glob paths to exclude (e.g: generated files)
+# package_overrides:
+#   api:
+#     release_message: "chore(api): release %s"
+#     tag_prefix: release-
+#     paths: ["packages/api/**", "proto/**"]
+#     exclude_paths: ["packages/api/generated/**"]
+#     depends_on: [lib]
+# Packages listed in depends_on are released first, a package is released with at least a patch whenever one of its
+# dependencies is released in the same run
+# Update the requirements on packages released in the same run in the npm and cargo bump files of their dependents
+# (e.g: the lib version in the package.json of app), plain version requirements keep their operator (e.g: ^1.2.0)
+# update_dependency_versions: true
+# Trailers appended to the release commit message
+# release_commit_trailers:
+#   - "Co-authored-by: Jane Doe <jane@example.com>"
+# Sign the release commit with GPG, required when branch protection enforces signed commits
+# sign_commits: true
+# GPG key ID used to sign the release commit and tag, the key of the git user.signingkey config is used when empty.
+# Overridden by --signing-key
+# signing_key: 3AA5C34371567BD2
+# Sign release tags with GPG, tags are signed with signing_key when it is defined
+# sign_tags: true
+# Message of the release tag - /%s/ will be replaced with the tag, /%d/ with the release date (YYYY-MM-DD) and /%n/
+# with the release notes, so git show and tag listings carry the changelog
+# tag_message: "Release %s (%d)"
+# tag_message: "%s\n\n%n"
+# Type of the release tag, annotated (default) or lightweight. Lightweight tags have no message, so tag_message is
+# not used, and git describe only finds them with --tags. Signed tags (sign_tags or signing_key) are always annotated
+# tag_type: lightweight
 # If defined changelog will be written to this file
 # changelog_file: CHANGELOG.md
+# Separator between the pre ID and its counter, use an empty string for versions like 1.0.0-rc1
+# note that without a separator counters above 9 will not sort correctly by semver rules (rc10 < rc9)
+# pre_id_separator: "."
+# Fail instead of assuming a first release when no previous release tag is found (e.g: tags were not fetched)
+# error_on_no_previous_release: true
+# Limit the first release to commits after a ref or a date (e.g: 2024-01-31), defaults to the full history
+# first_release_since: v0.9.0
+# Only fetch the tags matching the tag prefix of the released packages, speeds up syncing repos with many tags.
+# All tags are fetched when the tag prefix is empty
+# fetch_prefix_tags: true
 # Clean pre releases when a new release is published
 # clean_pre_releases: true
+# Commits reverted within the same release do not contribute to the bump (e.g: a reverted feat only bumps patch)
+# reverts_affect_bump: true
+# Stable release notes note the pre release each commit first shipped in (e.g: first released in 1.2.0-rc.0)
+# annotate_pre_releases: true
+# Keep commits with an empty description (e.g: "fix:") using the first line of their body or "(no description)"
+# allow_empty_description: true
+# Release a patch when only commits of hidden types (types without a bump, e.g: docs) are found
+# patch_on_hidden_only: true
+# Version scheme, semver (default) bumps versions from commit types and calver versions releases by date as
+# YEAR.PERIOD.MICRO, where the micro version increments within the same period and the commit types only decide
+# whether to release
+# versioning: calver
+# Date format of the YEAR.PERIOD part of calendar versions in the changelog_timezone, it must result in two numbers
+# without leading zeros to be a valid semver version. Monthly by default ("%Y.%-m", e.g: 2024.6.0), use "%G.%-V" for
+# weekly (ISO week year, so the first days of January stay in the last week of the previous year) or "%Y.%-j" for
+# daily periods
+# calver_format: "%Y.%-m"
+# Fail when the next release version would exceed this version (e.g: an unexpected major bump), pass
+# --allow-exceed-ceiling to release it anyway
+# max_version: 1.999.999
+# Only include a commit in a package release when more than half of the files it changes match the package paths
+# strict_path_attribution: true
+# Only release once this number of relevant commits have accumulated
+# min_commits: 1
+# Set always_release_breaking to true and breaking changes are released even when below the count
+# min_commits: { count: 5, always_release_breaking: true }
+# Truncate commit descriptions in release notes to this number of characters
+# max_desc_length: 100
+# Show the number of commits in each section title (e.g: ### Bug Fixes (7))
+# section_counts: true
+# Group breaking commits of types that are not configured under a single section instead of one section per type
+# unknown_type_section: Other Changes
+# Group commits with these scopes under the mapped section regardless of their type, a scope section takes
+# precedence over the type section and scope sections are listed before type sections
+# scope_sections:
+#   api: API Changes
+# If defined the released version (with build metadata when applied) is written to this file, also in dry runs.
+# The file is written to the folder of each package after its release commit, add it to .gitignore
+# write_version_file: .version
+# Include the tag prefix in the version written to write_version_file (e.g: v1.2.3)
+# version_file_prefix: true
+# Show the build metadata of bump files with build_metadata in the release notes header (e.g: ## 1.2.3+4)
+# header_include_metadata: true
+# Character used for list bullets in release notes: - or *
+# bullet_char: "-"
+# Number of spaces used to indent commits nested under a scope
+# nested_indent: 2
+# Link issue and pull request references (e.g: #123) of commit descriptions and footers (e.g: Closes #45), the
+# trailing reference of squash merges is moved to the end of the line
+# link_references: true
+# Path of the repository url references link to, other hosts than GitHub may need pull
+# references_path: issues
+# If defined release notes are rendered with this handlebars template instead of the built-in layout, it receives
+# version, previous_version, date, compare_url, sections (title, count, commits and scopes), breaking_changes and
+# new_contributors
+# changelog_template: .github/release-notes.hbs
+# Format of the release date in release notes (e.g: %Y-%m-%d)
+# changelog_date_format: "%b %_d, %Y"
+# Timezone of the release date in release notes: utc or local
+# changelog_timezone: utc
+# Publish a single GitHub release for all packages released in a run, tagged release-<date>
+# combined_release: true
+# If defined current versions and packages will be seeded from a release-please style manifest instead of git tags,
+# the manifest will be updated with the new versions on publish
+# manifest_file: .release-please-manifest.json
+# If defined the changelog, bump files, release commit and tag are written on this branch while commits are
+# analyzed on the current branch, which is checked out again once the release is published
+# bump_files_branch: release
+# If defined the current branch is pulled from this remote branch before releasing and the release commit is pushed
+# to it, required when releasing from a detached HEAD (e.g: CI checkouts)
+# branch: main
+# Where release notes are published besides the release: release (only the release), gist (secret gist) or wiki (page in
+# the repository wiki)
+# notes_target: release
+# How the token authenticates git remote commands: url embeds it in the remote url, header sends it with
+# http.extraHeader so it never appears in the url (safer in CI logs)
+# git_auth: url
+# Name of the git remote the repository url is read from and releases are pushed to
+# remote: origin
+# Where releases are published: github, gitlab or gitea (also Forgejo). GitLab releases are created with the token as
+# a PRIVATE-TOKEN, notes_target, create_deployment and --pr are only supported on github
+# provider: github
+# How git remote commands reach the remote: https pushes with the token, ssh pushes to the untouched ssh remote
+# url with the keys of the runner (e.g: deploy keys) and the token is only used for the API
+# transport: https
+# URL of the GitHub API, defaults to GITHUB_API_URL on GitHub Actions, api.github.com for github.com remotes and
+# https://<host>/api/v3 for GitHub Enterprise remotes. With gitlab it defaults to https://<host>/api/v4 and with gitea
+# to https://<host>/api/v1
+# api_url: https://git.example.com/api/v3
+# Number of times publishing a release and cleaning pre releases are retried when the API fails on the server side
+# (5xx) or rate limits (429), waiting exponentially longer between attempts or as long as Retry-After asks
+# api_max_retries: 3
+# Update the release of a tag with the new notes when the tag already exists remotely (e.g: a previous run failed after
+# publishing it) instead of failing, with --force the tag is recreated and its release updated instead of duplicated
+# update_existing: true
+# If defined released tags are marked as deployed to this GitHub environment
+# create_deployment: production
+# What to do when a bump file is not found on disk: error, skip or warn
+# on_missing_bump_file: error
+# Include the author of each commit in release notes, set to false to disable author based sections
+# include_authors: true
+# List authors contributing for the first time in a New Contributors section, requires include_authors
+# new_contributors: true
+# Credit co-authors (Co-authored-by footers) after the description of their commits, requires include_authors
+# co_authors: true
+# Commits made by these authors will be ignored (e.g: bots)
+# exclude_authors:
+#   - dependabot[bot]
+# Set to false and commits of excluded authors still count for the version bump, they are only left out of the notes
+# exclude_authors_from_bump: true
+# Commits whose subject or body contain this text are ignored, set to an empty string to disable
+# ignore_commit_pattern: "[skip release]"
+# If defined release types will be imported from a conventional-changelog config (.versionrc.json), only the JSON
+# form is supported. Field mapping: type -> commit_type, section -> section, hidden -> type without a bump.
+# Imported non reserved types bump patch, types defined below take precedence over imported ones.
+# compat_config: .versionrc.json
 # Allowed types that trigger a release and their corresponding semver bump
 # feat, fix and revert commit types are reserved types and can only have its section name changed
 # types:
 #   - { commit_type: feat, section: Features }
 #   - { commit_type: fix, section: Bug Fixes }
 #   - { commit_type: perf, bump: patch, section: Performance Improvements }
+# Types without a bump are hidden, their commits do not trigger a release (see patch_on_hidden_only)
+#   - { commit_type: docs, section: Documentation }
+# A build bump keeps the version and increments its build metadata when only build commits are released
+# (e.g: 1.2.3 -> 1.2.3+1). Bump files with build_metadata increment their own build number, other bump files
+# are written with the release version including its build metadata.
+#   - { commit_type: build, bump: build, section: Build System }
+# A major bump releases a major version for commits of the type without the ! or BREAKING CHANGE convention
+#   - { commit_type: breaking, bump: major, section: Breaking Changes }
+# Set changelog_file on a type and its sections will also be written to that file, set changelog_exclusive to true
+# to leave them out of the main changelog file. Release notes on GitHub always include every section.
+#   - { commit_type: security, bump: patch, section: Security, changelog_file: SECURITY.md }
+# Set emoji on a type to prefix its section title (e.g: ### ✨ Features)
+#   - { commit_type: feat, section: Features, emoji: "✨" }
+# Restart the build number at 1 whenever the version changes for bump files with build_metadata
+# reset_build_metadata_on_version_change: true
 # If defined will bump the version in this files, at least one file must be defined for a release to be published.
-# Supported versioning file targets are: cargo, npm, pub, android and ios.
+# Supported versioning file targets are: cargo, npm, composer, pub, python, helm, android, ios, custom and regex.
+# For cargo set update_lock to true and Cargo.lock is refreshed after the bump, failures only log a warning.
 # For android the path must be the root of the android folder.
 # For ios the path must be the root of the ios folder and the name of the project.
+# For helm the path is the chart folder, only the top level version is updated and appVersion too when app_version is true.
+# For python the path is the folder of pyproject.toml ([project] or [tool.poetry] version), a setup.cfg file ([metadata]
+# version) or a .py file with __version__.
 # Set the package property to true and the bump file parent folder will be treated as the root for commits made under
 # that folder and will have their own releases, this is useful for monorepos.
 # Set the build_metadata property to true and the version will be appended with a build number. (e.g: 1.0.0+1, 1.0.0-alpha.1+1, etc.)
-# For android and ios targets the build_metadata property will be ignored and the build number will be incremented.
+# For android targets the versionCode is incremented, with build_metadata it is also appended to the versionName.
+# For ios targets the build_metadata property will be ignored and the build number will be incremented.
+# The regex target is an alias of custom.
+# For custom targets the path is the file itself, pattern is a regex whose first capture group matches the version
+# and the optional template replaces the whole match with %s replaced by the version.
+# Set the date_key property and the release date will be written to that key of the same file, the date_format
+# property is a strftime format and defaults to %Y-%m-%d. For npm targets it is a top level JSON key, for the other
+# targets a `key = "..."` or `key: ...` line is replaced.
 # Use <root> as the path to target to let donder-release know that it should look for files where it was executed.
+# Set auto_detect_bump_files to true and when no bump files are defined the Cargo.toml, package.json,
+# pubspec.yaml and pyproject.toml found at the root are bumped.
+# auto_detect_bump_files: true
 # bump_files:
 #   - { target: cargo, path: <root> }
+#   - { target: cargo, path: <root>, update_lock: true }
 #   - { target: npm, path: <root> }
 #   - { target: npm, path: <root>, build_metadata: true }
+#   - { target: npm, path: <root>, date_key: releaseDate, date_format: "%Y-%m-%d" }
+#   - { target: composer, path: <root> }
 #   - { target: pub, path: <root> }
 #   - { target: pub, path: <root>, build_metadata: true }
+#   - { target: python, path: <root> }
+#   - { target: python, path: src/my_package/__version__.py }
+#   - { target: helm, path: charts/my-chart, app_version: true }
 #   - { target: android, path: "<root>" }
 #   - { target: android, path: android }
+#   - { target: android, path: android, build_metadata: true }
     # ios target requires the name of the project
 #   - { target: ios, path: my_app_name }
 #   - { target: ios, path: ios/my_app_name }
+#   - { target: custom, path: version.go, pattern: 'Version = "(.*)"' }
+#   - { target: regex, path: version.go, pattern: 'Version = "(.*)"' }
+#   - { target: custom, path: about.txt, pattern: 'v(\S+)', template: 'v%s' }
 #   - { target: npm, path: packages/a-package/package.json, package: true }
 #   - { target: npm, path: packages/b-package/package.json, package: true }
 "#;
@@ -73,33 +287,208 @@ pub struct Ctx {
     /// Prefix of the release tag
     #[serde(default = "default_tag_prefix")]
     pub tag_prefix: String,
+    /// Release message, tag prefix, commit paths and dependencies of packages keyed by package name
+    #[serde(default)]
+    pub package_overrides: HashMap<String, PackageOverride>,
+    /// Update dependency requirements on packages released in the same run
+    #[serde(default)]
+    pub update_dependency_versions: bool,
+    /// Trailers appended to the release commit message
+    #[serde(default)]
+    pub release_commit_trailers: Vec<String>,
+    /// Sign the release commit with GPG
+    #[serde(default)]
+    pub sign_commits: bool,
+    /// GPG key ID used to sign the release commit and tag
+    #[serde(default)]
+    pub signing_key: String,
+    /// Sign release tags with GPG
+    #[serde(default)]
+    pub sign_tags: bool,
+    /// Message of the release tag
+    #[serde(default = "default_tag_message")]
+    pub tag_message: String,
+    /// Type of the release tag (annotated or lightweight)
+    #[serde(default = "default_tag_type")]
+    pub tag_type: String,
     /// Allowed types of that trigger a release and their corresponding semver bump
     #[serde(default = "default_types")]
     pub types: ReleaseTypes,
+    /// If not empty release types will be imported from this conventional-changelog config
+    #[serde(default)]
+    pub compat_config: String,
+    /// Bump the manifests found at the root when no bump files are configured
+    #[serde(default)]
+    pub auto_detect_bump_files: bool,
     /// Allowed types of that trigger a release and their corresponding semver bump
     #[serde(default = "default_bump_files")]
     pub bump_files: BumpFiles,
+    /// Separator between the pre ID and its counter
+    #[serde(default = "default_pre_id_separator")]
+    pub pre_id_separator: String,
+    /// Commits with an empty description are kept instead of dropped
+    #[serde(default)]
+    pub allow_empty_description: bool,
+    /// Releases made only of commits of hidden types (types without a bump) release a patch
+    #[serde(default)]
+    pub patch_on_hidden_only: bool,
+    /// Version scheme (semver or calver)
+    #[serde(default = "default_versioning")]
+    pub versioning: String,
+    /// Date format of the YEAR.PERIOD part of calendar versions
+    #[serde(default = "default_calver_format")]
+    pub calver_format: String,
+    /// Releases above this version require --allow-exceed-ceiling
+    #[serde(default)]
+    pub max_version: String,
+    /// Commits belong to a package only when most of their changed files are under its path
+    #[serde(default)]
+    pub strict_path_attribution: bool,
+    /// Minimum number of relevant commits required to release
+    #[serde(default)]
+    pub min_commits: MinCommits,
+    /// Commits reverted in the same release do not contribute to the bump
+    #[serde(default)]
+    pub reverts_affect_bump: bool,
+    /// Stable release notes annotate commits that already shipped in pre releases
+    #[serde(default)]
+    pub annotate_pre_releases: bool,
+    /// Fail when no previous release is found instead of assuming a first release
+    #[serde(default)]
+    pub error_on_no_previous_release: bool,
+    /// Bounds the commits of the first release to those after this ref or date
+    #[serde(default)]
+    pub first_release_since: String,
+    /// Only fetch tags matching the tag prefix of the released packages
+    #[serde(default)]
+    pub fetch_prefix_tags: bool,
     /// Clean pre releases when a new release is published
     #[serde(default = "default_clean_pre_releases")]
     pub clean_pre_releases: bool,
+    /// Publish a single GitHub release for all packages released in a run
+    #[serde(default)]
+    pub combined_release: bool,
     /// Include authors in changelog
     #[serde(default = "default_include_authors")]
     pub include_authors: bool,
+    /// Render a New Contributors section with first time authors
+    #[serde(default)]
+    pub new_contributors: bool,
+    /// Commits made by these authors are ignored
+    #[serde(default)]
+    pub exclude_authors: Vec<String>,
+    /// Commits made by excluded authors do not count for the version bump
+    #[serde(default = "default_exclude_authors_from_bump")]
+    pub exclude_authors_from_bump: bool,
+    /// Commits whose subject or body contain this text are ignored
+    #[serde(default = "default_ignore_commit_pattern")]
+    pub ignore_commit_pattern: String,
     /// If not empty changelog will be written to this file
     #[serde(default)]
     pub changelog_file: String,
+    /// If not empty the released version is written to this file
+    #[serde(default)]
+    pub write_version_file: String,
+    /// The version written to write_version_file includes the tag prefix
+    #[serde(default)]
+    pub version_file_prefix: bool,
+    /// Release notes header includes the build metadata of the bump files
+    #[serde(default)]
+    pub header_include_metadata: bool,
+    /// Release notes rendering options
+    #[serde(flatten)]
+    pub notes: NotesOptions,
+    /// If not empty the release commit and tag are made on this branch
+    #[serde(default)]
+    pub bump_files_branch: String,
+    /// If not empty releases are pulled from and pushed to this remote branch instead of the current branch
+    #[serde(default)]
+    pub branch: String,
+    /// Where release notes are published besides the release (release, gist or wiki)
+    #[serde(default = "default_notes_target")]
+    pub notes_target: String,
+    /// Provider releases are published to (github, gitlab or gitea)
+    #[serde(default = "default_provider")]
+    pub provider: String,
+    /// How the token authenticates git remote commands (url or header)
+    #[serde(default = "default_git_auth")]
+    pub git_auth: String,
+    /// Name of the git remote releases are pushed to
+    #[serde(default = "default_remote")]
+    pub remote: String,
+    /// How git remote commands reach the remote (https or ssh)
+    #[serde(default = "default_transport")]
+    pub transport: String,
+    /// If not empty the GitHub API is reached at this URL instead of the one derived from the remote
+    #[serde(default)]
+    pub api_url: String,
+    /// Number of times release API requests are retried on server errors and rate limits
+    #[serde(default = "default_api_max_retries")]
+    pub api_max_retries: u32,
+    /// Update the release of a tag when it already exists instead of failing
+    #[serde(default)]
+    pub update_existing: bool,
+    /// If not empty released tags are marked as deployed to this environment
+    #[serde(default)]
+    pub create_deployment: String,
+    /// What to do when a bump file is not found (error, skip or warn)
+    #[serde(default = "default_on_missing_bump_file")]
+    pub on_missing_bump_file: String,
+    /// Restart build metadata numbering when the bumped version changes
+    #[serde(default)]
+    pub reset_build_metadata_on_version_change: bool,
+    /// If not empty current versions will be read from this release-please style manifest
+    #[serde(default)]
+    pub manifest_file: String,
     /// Release optional pre ID (e.g: alpha, beta, rc)
     #[serde(skip)]
     pub pre_id: String,
     /// When in preview mode, the release will not be published.
     #[serde(skip)]
     pub preview: bool,
+    /// Explain how the next release version is computed
+    #[serde(skip)]
+    pub explain: bool,
+    /// Fail when a commit in range does not follow the conventional format
+    #[serde(skip)]
+    pub strict: bool,
+    /// Release versions above max_version
+    #[serde(skip)]
+    pub allow_exceed_ceiling: bool,
+    /// Release this version instead of the one computed from commits
+    #[serde(skip)]
+    pub set_version: String,
+    /// Commit range of a pull request (base..head) used instead of the tag based range
+    #[serde(skip)]
+    pub pr_range: String,
+    /// Format of previewed release notes (markdown or json)
+    #[serde(skip)]
+    pub format: String,
+    /// Format of the run output (text or json)
+    #[serde(skip)]
+    pub output: String,
+    /// Recreate release tags that already exist on the remote
+    #[serde(skip)]
+    pub force: bool,
+    /// Publish without asking for confirmation
+    #[serde(skip)]
+    pub yes: bool,
+    /// When offline no network operation is made and the release is previewed
+    #[serde(skip)]
+    pub offline: bool,
+    /// When in dry run publish mode, the release is made locally and GitHub API access is validated, nothing is
+    /// pushed and no release is created.
+    #[serde(skip)]
+    pub dry_run_publish: bool,
     /// git api
     #[serde(skip)]
     pub git: Git,
     /// github api
     #[serde(skip)]
     pub api: GithubApi,
+    /// api of the provider releases are published to
+    #[serde(skip, default = "default_releases")]
+    pub releases: Box<dyn ReleaseProvider>,
     // packages to bump
     #[serde(skip)]
     pub packages: Vec<Pkg>,
@@ -109,6 +498,26 @@ fn default_release_message() -> String {
     "chore(release): %s".to_string()
 }
 
+fn default_tag_type() -> String {
+    "annotated".to_string()
+}
+
+fn default_tag_message() -> String {
+    "%s".to_string()
+}
+
+fn default_ignore_commit_pattern() -> String {
+    "[skip release]".to_string()
+}
+
+fn default_versioning() -> String {
+    "semver".to_string()
+}
+
+fn default_calver_format() -> String {
+    "%Y.%-m".to_string()
+}
+
 fn default_tag_prefix() -> String {
     "v".to_string()
 }
@@ -121,6 +530,14 @@ fn default_bump_files() -> BumpFiles {
     vec![]
 }
 
+fn default_pre_id_separator() -> String {
+    ".".to_string()
+}
+
+fn default_min_commits() -> usize {
+    1
+}
+
 fn default_clean_pre_releases() -> bool {
     false
 }
@@ -129,60 +546,93 @@ fn default_include_authors() -> bool {
     true
 }
 
+fn default_exclude_authors_from_bump() -> bool {
+    true
+}
+
+fn default_on_missing_bump_file() -> String {
+    "error".to_string()
+}
+
+fn default_api_max_retries() -> u32 {
+    3
+}
+
+fn default_provider() -> String {
+    "github".to_string()
+}
+
+fn default_releases() -> Box<dyn ReleaseProvider> {
+    Box::new(GithubApi::default())
+}
+
+fn default_git_auth() -> String {
+    "url".to_string()
+}
+
+fn default_remote() -> String {
+    "origin".to_string()
+}
+
+fn default_transport() -> String {
+    "https".to_string()
+}
+
+fn default_notes_target() -> String {
+    "release".to_string()
+}
+
 impl Ctx {
-    pub fn new(config: String, pre_id: String, preview: bool, selected_packages: Vec<String>) -> Result<Self> {
-        let config_path = path::PathBuf::from(config);
-        let file = fs::File::open(config_path).expect("could not open file");
-        let input_config: Ctx = serde_yaml::from_reader(file)
-            .expect("failed to parse file");
+    pub fn new(
+        config: String,
+        pre_id: String,
+        preview: bool,
+        dry_run_publish: bool,
+        selected_packages: Vec<String>,
+        version_only: bool,
+    ) -> Result<Self> {
+        let mut input_config = Ctx::load(&config)?;
+
+        // Every problem of the config is reported at once
+        let problems = input_config.problems(&pre_id, version_only);
+
+        if !problems.is_empty() {
+            bail!("{}", problems.join("\n"));
+        }
+
         let mut default_types = vec![
             ReleaseType {
                 commit_type: "feat".to_string(),
                 bump: "minor".to_string(),
                 section: "Features".to_string(),
+                ..Default::default()
             },
             ReleaseType {
                 commit_type: "fix".to_string(),
                 bump: "patch".to_string(),
                 section: "Bug Fixes".to_string(),
+                ..Default::default()
             },
             ReleaseType {
                 commit_type: "revert".to_string(),
                 bump: "patch".to_string(),
                 section: "Reverts".to_string(),
+                ..Default::default()
             },
         ];
 
         // Parse types
         for release_type in input_config.types {
-            // Protect fix, feat and revert types
-            if release_type.commit_type == "feat"
-                || release_type.commit_type == "fix"
-                || release_type.commit_type == "revert"
-            {
-                if !release_type.bump.is_empty() {
-                    bail!("feat, fix and perf are reserved types and cannot have a bump");
-                }
-            // Only allow minor and patch bumps
-            } else if release_type.bump != "minor" && release_type.bump != "patch" {
-                bail!("only minor and patch bumps are allowed");
-            }
-
-            // Protect type section from being empty
-            if release_type.section.is_empty() {
-                bail!("type section cannot be empty");
-            }
-
-            // Update default types
+            // Update default types keeping their reserved bumps
             match release_type.commit_type.as_str() {
                 "feat" => {
-                    default_types[0].section = release_type.section;
+                    default_types[0] = ReleaseType { bump: "minor".to_string(), ..release_type };
                 },
                 "fix" => {
-                    default_types[1].section = release_type.section;
+                    default_types[1] = ReleaseType { bump: "patch".to_string(), ..release_type };
                 },
                 "revert" => {
-                    default_types[2].section = release_type.section;
+                    default_types[2] = ReleaseType { bump: "patch".to_string(), ..release_type };
                 },
                 _ => {
                     default_types.push(release_type);
@@ -190,9 +640,26 @@ impl Ctx {
             }
         }
 
-        // Enforce at least one bump file
-        if input_config.bump_files.is_empty() {
-            bail!("at least one bump file must be defined");
+        // Authors are only credited when enabled
+        if !input_config.include_authors {
+            input_config.notes.co_authors = false;
+        }
+
+        // Register the manifests found at the repo root when no bump files are configured
+        if input_config.auto_detect_bump_files && input_config.bump_files.is_empty() {
+            let manifests = [
+                ("cargo", "Cargo.toml"),
+                ("npm", "package.json"),
+                ("pub", "pubspec.yaml"),
+                ("python", "pyproject.toml"),
+            ];
+
+            for (target, file) in manifests {
+                if path::Path::new(file).exists() {
+                    logInfo!("Auto detected bump file {}", file);
+                    input_config.bump_files.push(BumpFile::new(target, "<root>"));
+                }
+            }
         }
 
         let mut packages = HashMap::new();
@@ -206,26 +673,12 @@ impl Ctx {
             )?,
         );
 
-        // Protect bump files from unsupported targets
+        // Build packages list
         for bump_file in &input_config.bump_files {
-            if bump_file.target != "cargo"
-                && bump_file.target != "npm"
-                && bump_file.target != "pub"
-                && bump_file.target != "android"
-                && bump_file.target != "ios"
-            {
-                bail!("unsupported bump file target");
-            }
-
-            // Build packages list
             if bump_file.package {
                 // get package name from bump file path string
                 let segments = bump_file.path.split("/").collect::<Vec<&str>>();
 
-                if segments.len() < 2 {
-                    bail!("invalid bump file path for a package");
-                }
-                
                 // package name should be the second to last segment
                 let package_name = segments[segments.len() - 2].to_string();
 
@@ -248,8 +701,74 @@ impl Ctx {
             }
         }
 
-        // Remove root package if it has no bump files
-        if packages.get("root").unwrap().bump_files.is_empty() {
+        // Seed package list and current versions from manifest
+        let mut manifest_root = false;
+
+        if !input_config.manifest_file.is_empty() {
+            for (manifest_path, version) in read_manifest(&input_config.manifest_file)? {
+                if manifest_path == "." || manifest_path.is_empty() {
+                    manifest_root = true;
+                    packages.get_mut("root").unwrap().manifest_version = Some(version);
+                    continue;
+                }
+
+                match packages.values_mut().find(|pkg| pkg.path == manifest_path) {
+                    Some(pkg) => pkg.manifest_version = Some(version),
+                    None => {
+                        // package name should be the last segment of the manifest path
+                        let package_name = manifest_path.split("/").last().unwrap().to_string();
+
+                        if packages.contains_key(&package_name) {
+                            bail!("manifest package {} conflicts with an existing package", manifest_path);
+                        }
+
+                        let mut pkg = Pkg::new(
+                            package_name.clone(),
+                            manifest_path.clone(),
+                            input_config.tag_prefix.clone(),
+                            vec![],
+                        )?;
+                        pkg.manifest_version = Some(version);
+
+                        packages.insert(package_name, pkg);
+                    }
+                }
+            }
+        }
+
+        // Merge package overrides over the global release message, tag prefix and package path
+        for (package_name, pkg) in packages.iter_mut() {
+            let package_override = input_config.package_overrides.get(package_name);
+
+            if let Some(package_override) = package_override {
+                pkg.include_paths = package_override.paths.clone();
+                pkg.exclude_paths = package_override.exclude_paths.clone();
+                pkg.depends_on = package_override.depends_on.clone();
+            }
+
+            pkg.release_message = package_override
+                .and_then(|o| o.release_message.clone())
+                .unwrap_or(input_config.release_message.clone());
+
+            if let Some(tag_prefix) = package_override.and_then(|o| o.tag_prefix.as_ref()) {
+                pkg.set_tag_prefix(tag_prefix);
+            }
+        }
+
+        for (package_name, package_override) in &input_config.package_overrides {
+            if !packages.contains_key(package_name) {
+                bail!("package override {} does not match any package", package_name);
+            }
+
+            for dependency in &package_override.depends_on {
+                if dependency == package_name || dependency == "root" || !packages.contains_key(dependency) {
+                    bail!("package {} depends on invalid package {}", package_name, dependency);
+                }
+            }
+        }
+
+        // Remove root package if it has no bump files, unless it is the only package to compute a version for
+        if packages.get("root").unwrap().bump_files.is_empty() && !manifest_root && !(version_only && packages.len() == 1) {
             packages.remove("root");
         }
 
@@ -267,43 +786,396 @@ impl Ctx {
             bail!("no packages to release make sure you have selected packages defined in your config file");
         }
 
-        let token = std::env::var("GH_TOKEN").unwrap_or("".to_string());
+        // Release dependencies before the packages that depend on them
+        let collected_packages = sort_by_dependencies(collected_packages)?;
+
+        let mut token = std::env::var("GH_TOKEN").unwrap_or("".to_string());
+
+        // Fallback to the token provided by GitHub Actions
+        if token.is_empty() {
+            token = actions_var("GITHUB_TOKEN");
+        }
 
         // if token is empty and we are not in preview mode bail
         if token.is_empty() && !preview {
             bail!("GH_TOKEN environment variable is not defined");
         }
 
-        let git_api = Git::new(
+        let mut git_api = Git::new(
             &token,
             &std::env::var("GIT_AUTHOR_NAME").unwrap_or("sbayw-bot".to_string()),
             &std::env::var("GIT_AUTHOR_EMAIL").unwrap_or("support@southbaywarriors.com".to_string()),
+            &input_config.git_auth,
+            &input_config.remote,
+            &input_config.transport,
+            &input_config.provider,
         ).context("failed to create git api")?;
 
+        git_api.sign_commits = input_config.sign_commits;
+        git_api.sign_tags = input_config.sign_tags;
+        git_api.lightweight_tags = input_config.tag_type == "lightweight";
+
         let github_api = GithubApi::new(
             &token,
             &git_api.owner,
             &git_api.repo,
-        );
+            &input_config.api_url,
+            &git_api.host,
+        ).with_max_retries(input_config.api_max_retries);
+
+        let releases: Box<dyn ReleaseProvider> = match input_config.provider.as_str() {
+            "gitlab" => Box::new(GitLabApi::new(
+                &token,
+                &git_api.owner,
+                &git_api.repo,
+                &input_config.api_url,
+                &git_api.host,
+            ).with_max_retries(input_config.api_max_retries)),
+            "gitea" => Box::new(GiteaApi::new(
+                &token,
+                &git_api.owner,
+                &git_api.repo,
+                &input_config.api_url,
+                &git_api.host,
+            ).with_max_retries(input_config.api_max_retries)),
+            _ => Box::new(github_api.clone()),
+        };
 
         Ok(
             Self {
                 preview,
+                dry_run_publish,
                 pre_id,
                 git: git_api,
                 api: github_api,
+                releases,
                 types: default_types,
                 packages: collected_packages,
                 ..input_config
             }
         )
     }
+
+    /// Reads a config file, with the types of its conventional-changelog config when defined
+    pub fn load(config: &str) -> Result<Self> {
+        let config_path = path::PathBuf::from(config);
+        let file = fs::File::open(config_path).context(format!("could not open {}", config))?;
+        let mut input_config: Ctx = serde_yaml::from_reader(file)
+            .context(format!("failed to parse {}", config))?;
+
+        // Import types from conventional-changelog config, explicit types take precedence
+        if !input_config.compat_config.is_empty() {
+            let mut types = read_compat_types(&input_config.compat_config)?;
+
+            types.retain(|t| !input_config.types.iter().any(|r| r.commit_type == t.commit_type));
+            types.append(&mut input_config.types);
+            input_config.types = types;
+        }
+
+        Ok(input_config)
+    }
+
+    /// Every problem found in a loaded config, empty when it is valid
+    pub fn problems(&self, pre_id: &str, version_only: bool) -> Vec<String> {
+        let mut problems = vec![];
+
+        for release_type in &self.types {
+            // Protect fix, feat and revert types
+            if release_type.commit_type == "feat"
+                || release_type.commit_type == "fix"
+                || release_type.commit_type == "revert"
+            {
+                if !release_type.bump.is_empty() {
+                    problems.push(format!("{} is a reserved type and cannot have a bump", release_type.commit_type));
+                }
+            // Only allow major, minor, patch and build bumps, types without a bump are hidden
+            } else if !["major", "minor", "patch", "build", ""].contains(&release_type.bump.as_str()) {
+                problems.push(format!(
+                    "type {} bump must be one of major, minor, patch or build",
+                    release_type.commit_type,
+                ));
+            }
+
+            // Protect type section from being empty
+            if release_type.section.is_empty() {
+                problems.push(format!("type {} section cannot be empty", release_type.commit_type));
+            }
+        }
+
+        // Enforce at least one bump file, computing the next version alone does not write any
+        if self.bump_files.is_empty() && !version_only {
+            problems.push("at least one bump file must be defined".to_string());
+        }
+
+        // Protect pre release from resulting in an invalid semver pre release
+        if !pre_id.is_empty() && Prerelease::new(&format!("{}{}0", pre_id, self.pre_id_separator)).is_err() {
+            problems.push("pre ID and pre ID separator must form a valid semver pre release".to_string());
+        }
+
+        // Protect release commit trailers from invalid formats
+        let trailer_re = Regex::new(r"^[A-Za-z0-9][A-Za-z0-9-]*: \S.*$").unwrap();
+        for trailer in &self.release_commit_trailers {
+            if !trailer_re.is_match(trailer) {
+                problems.push(format!("invalid release commit trailer \"{}\", expected \"Key: value\"", trailer));
+            }
+        }
+
+        // Protect missing bump file handling from unsupported values
+        if self.on_missing_bump_file != "error"
+            && self.on_missing_bump_file != "skip"
+            && self.on_missing_bump_file != "warn"
+        {
+            problems.push("on_missing_bump_file must be one of error, skip or warn".to_string());
+        }
+
+        if !self.max_version.is_empty() && Version::parse(&self.max_version).is_err() {
+            problems.push(format!("max_version {} is not a valid semver version", self.max_version));
+        }
+
+        if !self.notes.changelog_template.is_empty()
+            && !path::Path::new(&self.notes.changelog_template).exists()
+        {
+            problems.push(format!("changelog_template {} not found", self.notes.changelog_template));
+        }
+
+        // Protect release notes dates from invalid formats
+        if StrftimeItems::new(&self.notes.changelog_date_format).any(|item| item == Item::Error) {
+            problems.push(format!("invalid changelog_date_format {}", self.notes.changelog_date_format));
+        }
+
+        if self.notes.changelog_timezone != "utc" && self.notes.changelog_timezone != "local" {
+            problems.push("changelog_timezone must be one of utc or local".to_string());
+        }
+
+        if self.tag_type != "annotated" && self.tag_type != "lightweight" {
+            problems.push("tag_type must be one of annotated or lightweight".to_string());
+        }
+
+        if self.versioning != "semver" && self.versioning != "calver" {
+            problems.push("versioning must be one of semver or calver".to_string());
+        }
+
+        // Protect calendar versions from formats that are not valid semver
+        if self.versioning == "calver" {
+            let period_re = Regex::new(r"^(0|[1-9]\d*)\.(0|[1-9]\d*)$").unwrap();
+
+            if StrftimeItems::new(&self.calver_format).any(|item| item == Item::Error)
+                || !period_re.is_match(&Local::now().format(&self.calver_format).to_string())
+            {
+                problems.push(format!(
+                    "calver_format {} must result in two numbers without leading zeros (e.g: %Y.%-m)",
+                    self.calver_format,
+                ));
+            }
+        }
+
+        // Protect markdown rendering from unsupported bullets
+        if self.notes.bullet_char != "-" && self.notes.bullet_char != "*" {
+            problems.push("bullet_char must be one of - or *".to_string());
+        }
+
+        // Protect git authentication from unsupported modes
+        if self.git_auth != "url" && self.git_auth != "header" {
+            problems.push("git_auth must be one of url or header".to_string());
+        }
+
+        if self.transport != "https" && self.transport != "ssh" {
+            problems.push("transport must be one of https or ssh".to_string());
+        }
+
+        if self.provider != "github" && self.provider != "gitlab" && self.provider != "gitea" {
+            problems.push("provider must be one of github, gitlab or gitea".to_string());
+        }
+
+        // Gists, deployments and wiki pages are GitHub features
+        if self.provider != "github"
+            && (self.notes_target != "release" || !self.create_deployment.is_empty())
+        {
+            problems.push("notes_target and create_deployment are only supported by the github provider".to_string());
+        }
+
+        // Protect release notes publishing from unsupported targets
+        if self.notes_target != "release"
+            && self.notes_target != "gist"
+            && self.notes_target != "wiki"
+        {
+            problems.push("notes_target must be one of release, gist or wiki".to_string());
+        }
+
+        for bump_file in &self.bump_files {
+            // Protect bump files from unsupported targets
+            if bump_file.target != "cargo"
+                && bump_file.target != "npm"
+                && bump_file.target != "composer"
+                && bump_file.target != "pub"
+                && bump_file.target != "python"
+                && bump_file.target != "helm"
+                && bump_file.target != "android"
+                && bump_file.target != "ios"
+                && bump_file.target != "custom"
+                && bump_file.target != "regex"
+            {
+                problems.push(format!("unsupported bump file target {}", bump_file.target));
+            }
+
+            // Protect date stamps from invalid formats
+            if !bump_file.date_key.is_empty()
+                && StrftimeItems::new(&bump_file.date_format).any(|item| item == Item::Error)
+            {
+                problems.push(format!("invalid date format for bump file {}", bump_file.path));
+            }
+
+            // Protect custom targets from invalid patterns
+            if bump_file.target == "custom" || bump_file.target == "regex" {
+                match Regex::new(&bump_file.pattern).ok().map(|re| re.captures_len()) {
+                    None => problems.push(format!("invalid pattern for bump file {}", bump_file.path)),
+                    Some(groups) if bump_file.pattern.is_empty() || groups < 2 => problems.push(format!(
+                        "pattern for bump file {} must have a version capture group",
+                        bump_file.path,
+                    )),
+                    Some(_) => (),
+                }
+            }
+
+            if bump_file.package && bump_file.path.split("/").count() < 2 {
+                problems.push(format!("invalid bump file path {} for a package", bump_file.path));
+            }
+        }
+
+        problems
+    }
+
+    /// Message of a release tag, notes are replaced last so their content is kept as is
+    pub fn tag_message(&self, tag: &str, notes: &str) -> String {
+        self.tag_message
+            .replace("%s", tag)
+            .replace("%d", &Local::now().format("%Y-%m-%d").to_string())
+            .replace("%n", notes.replace("\r\n", "\n").trim())
+    }
 }
 
+/// Orders packages by name so that every package comes after the packages it depends on
+fn sort_by_dependencies(mut packages: Vec<Pkg>) -> Result<Vec<Pkg>> {
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
 
-pub type ReleaseTypes = Vec<ReleaseType>;
+    let mut sorted: Vec<Pkg> = vec![];
+
+    while !packages.is_empty() {
+        // Dependencies that are not selected are not waited for
+        let ready = packages.iter().position(|pkg| {
+            pkg.depends_on
+                .iter()
+                .all(|dependency| !packages.iter().any(|p| &p.name == dependency))
+        });
+
+        match ready {
+            Some(index) => sorted.push(packages.remove(index)),
+            None => bail!(
+                "circular dependency between packages {}",
+                packages.iter().map(|pkg| pkg.name.clone()).collect::<Vec<String>>().join(", "),
+            ),
+        }
+    }
+
+    Ok(sorted)
+}
+
+/// Reads release types from a conventional-changelog config (.versionrc.json)
+fn read_compat_types(file_path: &str) -> Result<ReleaseTypes> {
+    let content = fs::read_to_string(file_path)
+        .context(format!("failed to read compat config {}", file_path))?;
+    let compat_config: CompatConfig = serde_json::from_str(&content)
+        .context(format!("failed to parse compat config {}", file_path))?;
+
+    let types = compat_config.types
+        .into_iter()
+        .map(|t| ReleaseType {
+            // Hidden types do not show up in the changelog so they do not trigger a release
+            bump: match t.commit_type.as_str() {
+                "feat" | "fix" | "revert" => "".to_string(),
+                _ if t.hidden => "".to_string(),
+                _ => "patch".to_string(),
+            },
+            section: t.section.unwrap_or(t.commit_type.clone()),
+            commit_type: t.commit_type,
+            ..Default::default()
+        })
+        .collect();
+
+    Ok(types)
+}
+
+#[derive(Debug, Deserialize)]
+struct CompatConfig {
+    #[serde(default)]
+    types: Vec<CompatType>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompatType {
+    #[serde(rename = "type")]
+    commit_type: String,
+    section: Option<String>,
+    #[serde(default)]
+    hidden: bool,
+}
 
+/// Minimum number of relevant commits required to release, a count or a count with its options
 #[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum MinCommits {
+    Count(usize),
+    Options {
+        #[serde(default = "default_min_commits")]
+        count: usize,
+        /// Breaking changes are released even below the count
+        #[serde(default, alias = "alwaysReleaseBreaking")]
+        always_release_breaking: bool,
+    },
+}
+
+impl Default for MinCommits {
+    fn default() -> Self {
+        MinCommits::Count(default_min_commits())
+    }
+}
+
+impl MinCommits {
+    pub fn count(&self) -> usize {
+        match self {
+            MinCommits::Count(count) => *count,
+            MinCommits::Options { count, .. } => *count,
+        }
+    }
+
+    pub fn always_release_breaking(&self) -> bool {
+        match self {
+            MinCommits::Count(_) => false,
+            MinCommits::Options { always_release_breaking, .. } => *always_release_breaking,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PackageOverride {
+    /// Release message of the release commit of the package
+    pub release_message: Option<String>,
+    /// Prefix of the release tag of the package
+    pub tag_prefix: Option<String>,
+    /// Glob paths of the files whose commits belong to the package, the package path when empty
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// Glob paths of the files whose commits do not belong to the package
+    #[serde(default)]
+    pub exclude_paths: Vec<String>,
+    /// Names of the packages the package is released with
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+}
+
+pub type ReleaseTypes = Vec<ReleaseType>;
+
+#[derive(Debug, Default, Deserialize)]
 pub struct ReleaseType {
     /// Type of the commit
     pub commit_type: String,
@@ -312,4 +1184,30 @@ pub struct ReleaseType {
     pub bump: String,
     /// Section of the changelog
     pub section: String,
+    /// If not empty sections of this type will also be written to this changelog file
+    #[serde(default)]
+    pub changelog_file: String,
+    /// Sections of this type are only written to their own changelog file
+    #[serde(default)]
+    pub changelog_exclusive: bool,
+    /// If not empty the section title is prefixed with this emoji (e.g: ✨)
+    #[serde(default)]
+    pub emoji: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn problems_are_collected_at_once() {
+        let config: Ctx = serde_yaml::from_str("{ tag_type: signed, versioning: romver, bullet_char: + }").unwrap();
+
+        assert_eq!(config.problems("", false), vec![
+            "at least one bump file must be defined",
+            "tag_type must be one of annotated or lightweight",
+            "versioning must be one of semver or calver",
+            "bullet_char must be one of - or *",
+        ]);
+    }
 }
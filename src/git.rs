@@ -1,46 +1,135 @@
-use anyhow::{Result, Ok, bail};
+use anyhow::{Context, Result, Ok, bail};
 use semver::Version;
-use std::process::Command;
+use std::{collections::HashMap, process::Command};
 use regex::Regex;
+use chrono::Utc;
+use base64::{Engine as _, engine::general_purpose};
+
+use crate::actions::actions_var;
 
 #[derive(Debug, Default)]
 pub struct Git {
     repo_url: String,
-    token: String,
+    // sent with remote commands instead of embedding the token in the url
+    auth_header: String,
     author: String,
     email: String,
+    pub host: String,
     pub owner: String,
     pub repo: String,
+    /// GPG key used to sign release commits and tags, the ambient key is used when empty
+    pub signing_key: String,
+    /// Sign release commits even without a selected key
+    pub sign_commits: bool,
+    /// Sign release tags even without a selected key
+    pub sign_tags: bool,
+    /// Create release tags without annotation, unless they are signed
+    pub lightweight_tags: bool,
 }
 
 impl Git {
-    pub fn new(token: &str, author: &str, email: &str) -> Result<Self> {
+    pub fn new(
+        token: &str,
+        author: &str,
+        email: &str,
+        git_auth: &str,
+        remote: &str,
+        transport: &str,
+        provider: &str,
+    ) -> Result<Self> {
         let origin_url = Command::new("git")
             .arg("config")
             .arg("--get")
-            .arg("remote.origin.url")
+            .arg(format!("remote.{}.url", remote))
             .output()
             .expect("[get_origin_url] failed to get origin url");
 
-        let origin_url = String::from_utf8_lossy(&origin_url.stdout).trim().to_string();
+        let mut origin_url = String::from_utf8_lossy(&origin_url.stdout).trim().to_string();
+
+        // GitHub Actions provides the repository when there is no origin remote
+        if origin_url.is_empty() && remote == "origin" && !actions_var("GITHUB_REPOSITORY").is_empty() {
+            origin_url = format!("{}/{}", actions_var("GITHUB_SERVER_URL"), actions_var("GITHUB_REPOSITORY"));
+        }
+
+        if origin_url.is_empty() {
+            bail!("git remote {} not found", remote);
+        }
 
         // get host, owner and repo from git remote url with regex
         let re = Regex::new(r"(git@|https://)([\w\.@]+)(/|:)([\w,\-,_]+)/([\w,\-,_]+)(.git){0,1}((/){0,1})").unwrap();
-        let caps = re.captures(&origin_url).unwrap();
+        let caps = re.captures(&origin_url).context(format!("failed to parse remote {} url", remote))?;
+
+        // SSH remotes authenticate with the keys of the runner, the token is only used for the API
+        if transport == "ssh" && &caps[1] != "git@" {
+            bail!("transport ssh requires an ssh remote (e.g: git@github.com:owner/repo.git), found {}", origin_url);
+        }
+
+        // GitLab and Gitea expect the token as the password of any user
+        let credentials = match provider {
+            "gitlab" | "gitea" => format!("oauth2:{}", token),
+            _ => token.to_string(),
+        };
+
+        // The token is either embedded in the url or sent as an http.extraHeader so it never shows in the url
+        let (repo_url, auth_header) = match git_auth {
+            _ if transport == "ssh" => (origin_url.clone(), String::new()),
+            "header" => (
+                format!("https://{}/{}/{}.git", &caps[2], &caps[4], &caps[5]),
+                format!(
+                    "Authorization: Basic {}",
+                    general_purpose::STANDARD.encode(match provider {
+                        "gitlab" | "gitea" => credentials,
+                        _ => format!("x-access-token:{}", token),
+                    }),
+                ),
+            ),
+            _ => (format!("https://{}@{}/{}/{}.git", credentials, &caps[2], &caps[4], &caps[5]), String::new()),
+        };
 
         Ok(
             Self {
-                repo_url: format!("https://{}@{}/{}/{}.git", token, &caps[2], &caps[4], &caps[5]),
-                token: token.to_string(),
+                repo_url,
+                auth_header,
                 author: author.to_string(),
                 email: email.to_string(),
+                // credentials of the remote url are not part of the host
+                host: caps[2].rsplit('@').next().unwrap_or(&caps[2]).to_string(),
                 owner: caps[4].to_string(),
                 repo: caps[5].to_string(),
+                signing_key: String::new(),
+                sign_commits: false,
+                sign_tags: false,
+                lightweight_tags: false,
             }
         )
     }
 
-    pub fn sync(&self) -> Result<()> {
+    // check that a signing key is available to gpg
+    pub fn check_signing_key(&self, key: &str) -> Result<()> {
+        let output = Command::new("gpg")
+            .args(["--list-secret-keys", key])
+            .output()
+            .context("failed to run gpg")?;
+
+        if !output.status.success() {
+            bail!("signing key {} not found", key);
+        }
+
+        Ok(())
+    }
+
+    // git command that talks to the remote
+    fn remote_command(&self) -> Command {
+        let mut command = Command::new("git");
+
+        if !self.auth_header.is_empty() {
+            command.args(["-c", &format!("http.extraHeader={}", self.auth_header)]);
+        }
+
+        command
+    }
+
+    pub fn sync(&self, offline: bool, tag_prefixes: &[String], branch: &str) -> Result<()> {
         let output = Command::new("git")
             .arg("status")
             .output()
@@ -58,19 +147,88 @@ impl Git {
         //     .args(["pull", &self.repo_url])
         //     .output()?;
 
+        // local tags are used as is when offline
+        if offline {
+            return Ok(());
+        }
+
+        // fast forward the release branch when it is explicit, the user must make sure it did not diverge
+        if !branch.is_empty() {
+            let output = self.remote_command()
+                .args(["pull", "--ff-only", &self.repo_url, branch])
+                .output()?;
+
+            if !output.status.success() {
+                bail!("failed to pull {}: {}", branch, String::from_utf8_lossy(&output.stderr));
+            }
+        }
+
+        // fetch only the tags matching the given prefixes, all tags otherwise
+        if !tag_prefixes.is_empty() {
+            let refspecs = tag_prefixes
+                .iter()
+                .map(|prefix| format!("refs/tags/{}*:refs/tags/{}*", prefix, prefix))
+                .collect::<Vec<String>>();
+
+            let output = self.remote_command()
+                .args(["fetch", "--prune", "--prune-tags", &self.repo_url])
+                .args(refspecs)
+                .output()?;
+
+            if !output.status.success() {
+                bail!("failed to fetch tags: {}", String::from_utf8_lossy(&output.stderr));
+            }
+
+            return Ok(());
+        }
+
         // fetch tags from remote
-        Command::new("git")
+        let output = self.remote_command()
             .args(["fetch", "--prune", "--prune-tags", &self.repo_url])
             .output()?;
 
+        if !output.status.success() {
+            bail!("failed to fetch tags: {}", String::from_utf8_lossy(&output.stderr));
+        }
+
+        Ok(())
+    }
+
+    pub fn current_branch(&self) -> Result<String> {
+        let output = Command::new("git")
+            .args(["rev-parse", "--abbrev-ref", "HEAD"])
+            .output()?;
+
+        if !output.status.success() {
+            bail!("failed to get current branch");
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    pub fn checkout(&self, branch: &str) -> Result<()> {
+        let output = Command::new("git")
+            .args(["checkout", branch])
+            .output()?;
+
+        if !output.status.success() {
+            bail!("failed to checkout {}: {}", branch, String::from_utf8_lossy(&output.stderr));
+        }
+
         Ok(())
     }
 
+    // checkout a branch and return the branch that was checked out before
+    pub fn switch_branch(&self, branch: &str) -> Result<String> {
+        let current_branch = self.current_branch()?;
+
+        self.checkout(branch)?;
+
+        Ok(current_branch)
+    }
+
     pub fn origin_url(&self) -> Result<String> {
-        let url = self.repo_url
-            .replace(&format!("{}@", self.token), "")
-            .replace(".git", "");
-        Ok(url)
+        Ok(format!("https://{}/{}/{}", self.host, self.owner, self.repo))
     }
 
     pub fn get_tags(&self, prefix: &str) -> Result<Vec<ReleaseInfo>> {
@@ -87,7 +245,7 @@ impl Git {
         let mut tags = output.split_whitespace().collect::<Vec<&str>>();
 
         tags.retain(
-                |tag| tag.starts_with(prefix) && Version::parse(&tag.replace(prefix, "")).is_ok()
+                |tag| tag.strip_prefix(prefix).is_some_and(|version| Version::parse(version).is_ok())
             );
 
         // map tags to tag info
@@ -114,61 +272,183 @@ impl Git {
         Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
     }
 
-    pub fn get_commits(&self, tag_head: &str, package_path: &str) -> Result<Vec<Commit>> {
-        // get commits between tag_head and HEAD
-        let output = match tag_head.is_empty() {
-            true => match package_path.is_empty() {
-                true => Command::new("git")
-                    .args(["log", "--pretty=format:\"%h|||%s|||%b\""])
-                    .output()
-                    .expect("[get_commits] failed to fetch"),
-                false => Command::new("git")
-                    .args(["log", "--pretty=format:\"%h|||%s|||%b\"", package_path])
-                    .output()
-                    .expect("[get_commits] failed to fetch"),
-            },
-            false => match package_path.is_empty() {
-                true => Command::new("git")
-                    .args(["log", "--pretty=format:\"%h|||%s|||%b\"", &format!("{}..HEAD", tag_head)])
-                    .output()
-                    .expect("[get_commits] failed to fetch"),
-                false => Command::new("git")
-                    .args(["log", "--pretty=format:\"%h|||%s|||%b\"", &format!("{}..HEAD", tag_head), "--", package_path])
-                    .output()
-                    .expect("[get_commits] failed to fetch"),
-            }
-        };
+    // get commits of a revision range (e.g: base..head), all commits when empty
+    pub fn get_range_commits(&self, range: &str, pathspecs: &[String]) -> Result<Vec<Commit>> {
+        // Fields are separated by NUL and commits by the record separator, neither can appear in commit messages
+        let mut args = vec!["log", "--pretty=format:%h%x00%H%x00%an%x00%s%x00%b%x1e"];
 
-        let output = String::from_utf8_lossy(&output.stdout).to_string();
+        if !range.is_empty() {
+            args.push(range);
+        }
 
-        let commits = output
-            .split("\n")
-            .map(|commit| {
-                let commit = commit.trim_matches(|c| c == '\"').split("|||").collect::<Vec<&str>>();
-                match commit.len() {
-                    3 => Commit::new(commit[0], commit[1], commit[2]),
-                    2 => Commit::new(commit[0], "", ""),
-                    _ => Commit::new("", "", ""),
-                }
-            })
-            .collect::<Vec<Commit>>();
+        // limit to the commits changing files matched by the pathspecs (e.g: packages/api, :(exclude)docs)
+        if !pathspecs.is_empty() {
+            args.push("--");
+            args.extend(pathspecs.iter().map(|p| p.as_str()));
+        }
+
+        let output = Command::new("git")
+            .args(args)
+            .output()
+            .context("failed to fetch commits")?;
 
-        Ok(commits)
+        if !output.status.success() {
+            bail!("failed to get commits of {} - {}", range, String::from_utf8_lossy(&output.stderr).trim());
+        }
+
+        Ok(parse_commits(&String::from_utf8_lossy(&output.stdout)))
     }
 
-    pub fn tag(&self, tag: &str) -> Result<()> {
+    // files changed by each commit of a revision range keyed by full hash, only the ones matching the pathspecs when given
+    pub fn changed_files(&self, range: &str, pathspecs: &[String]) -> Result<HashMap<String, Vec<String>>> {
+        let mut args = vec!["log", "--name-only", "--format=%x00%H"];
+
+        if !range.is_empty() {
+            args.push(range);
+        }
+
+        args.push("--");
+        args.extend(pathspecs.iter().map(|p| p.as_str()));
+
         let output = Command::new("git")
-            .args(["tag", "-a", tag, "-m", tag])
+            .args(args)
             .output()?;
 
         if !output.status.success() {
-            bail!("failed to tag");
+            bail!("failed to get files changed in {}", range);
+        }
+
+        Ok(
+            String::from_utf8_lossy(&output.stdout)
+                .split('\0')
+                .filter_map(|record| {
+                    let mut lines = record.lines();
+                    let hash = lines.next()?.trim();
+                    let files = lines
+                        .filter(|file| !file.trim().is_empty())
+                        .map(|file| file.to_string())
+                        .collect();
+
+                    Some((hash.to_string(), files))
+                })
+                .filter(|(hash, _)| !hash.is_empty())
+                .collect()
+        )
+    }
+
+    // names of every author reachable from a revision
+    pub fn authors(&self, revision: &str) -> Result<Vec<String>> {
+        let output = Command::new("git")
+            .args(["log", "--format=%an", revision])
+            .output()?;
+
+        if !output.status.success() {
+            bail!("failed to get authors of {}", revision);
+        }
+
+        Ok(
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(|author| author.to_string())
+                .collect()
+        )
+    }
+
+    // fetch the head of a pull request so its commits are available locally
+    pub fn fetch_pull_request(&self, number: u64) -> Result<()> {
+        let output = self.remote_command()
+            .args(["fetch", &self.repo_url, &format!("pull/{}/head", number)])
+            .output()?;
+
+        if !output.status.success() {
+            bail!("failed to fetch pull request {}: {}", number, String::from_utf8_lossy(&output.stderr));
         }
 
         Ok(())
     }
 
-    pub fn commit(&self, message: &str) -> Result<()> {
+    // last commit made before a date (e.g: 2024-01-31), empty when there is none
+    pub fn commit_before(&self, date: &str) -> Result<String> {
+        let output = Command::new("git")
+            .args(["rev-list", "-1", &format!("--before={}", date), "HEAD"])
+            .output()?;
+
+        if !output.status.success() {
+            bail!("failed to get commit before {}", date);
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    // run level tag for combined releases, suffixed when a release was already made on the same day
+    pub fn combined_release_tag(&self) -> Result<String> {
+        let base_tag = format!("release-{}", Utc::now().format("%Y-%m-%d"));
+        let mut tag = base_tag.clone();
+        let mut count = 1;
+
+        while self.tag_head(&tag).is_ok() {
+            tag = format!("{}.{}", base_tag, count);
+            count += 1;
+        }
+
+        Ok(tag)
+    }
+
+    // short hashes of the commits in a revision range (e.g: v1.0.0..v1.1.0-rc.0)
+    pub fn commit_hashes(&self, range: &str, pathspecs: &[String]) -> Result<Vec<String>> {
+        let mut args = vec!["log", "--pretty=format:%h", range];
+
+        if !pathspecs.is_empty() {
+            args.push("--");
+            args.extend(pathspecs.iter().map(|p| p.as_str()));
+        }
+
+        let output = Command::new("git")
+            .args(args)
+            .output()?;
+
+        if !output.status.success() {
+            bail!("failed to get commits of {}", range);
+        }
+
+        Ok(
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(|hash| hash.trim().to_string())
+                .collect()
+        )
+    }
+
+    pub fn tag(&self, tag: &str, message: &str) -> Result<()> {
+        let signed = self.sign_tags || !self.signing_key.is_empty();
+
+        // Signed tags are always annotated, their message is kept verbatim so markdown headings (#) survive
+        let mut args = match self.lightweight_tags && !signed {
+            true => vec!["tag", tag],
+            false => vec!["tag", "-a", tag, "--cleanup=verbatim", "-m", message],
+        };
+
+        // Signed with the selected key or the key of the git config
+        match self.signing_key.is_empty() {
+            true if self.sign_tags => args.push("-s"),
+            false => args.extend(["-u", &self.signing_key]),
+            _ => (),
+        }
+
+        let output = Command::new("git")
+            .args(args)
+            .output()?;
+
+        if !output.status.success() {
+            // Nothing is left behind when signing fails
+            self.undo_tag(tag).ok();
+            bail!(format!("failed to tag: {}", String::from_utf8_lossy(&output.stderr)));
+        }
+
+        Ok(())
+    }
+
+    pub fn commit(&self, message: &str, trailers: &[String]) -> Result<()> {
         let output = Command::new("git")
             .args(["add", "--all",])
             .output()?;
@@ -177,21 +457,113 @@ impl Git {
             bail!("failed to add changes");
         }
 
+        // Trailers are appended to the message body (e.g: Co-authored-by: Name <email>)
+        let message = match trailers.is_empty() {
+            true => message.to_string(),
+            false => format!("{}\n\n{}", message, trailers.join("\n")),
+        };
+
+        let mut args = vec![
+            "commit".to_string(),
+            format!("--author=\"{} <{}>\"", self.author, self.email),
+            "-m".to_string(),
+            message,
+        ];
+
+        // Signed with the selected key or the key of the git config
+        let signed = self.sign_commits || !self.signing_key.is_empty();
+        match self.signing_key.is_empty() {
+            true if signed => args.push("-S".to_string()),
+            false => args.push(format!("--gpg-sign={}", self.signing_key)),
+            _ => (),
+        }
+
         let output = Command::new("git")
-            .args(["commit", &format!("--author=\"{} <{}>\"", self.author, self.email), "-m", message])
+            .args(args)
             .output()?;
 
         if !output.status.success() {
+            if signed && self.signing_key.is_empty() {
+                bail!(format!(
+                    "failed to sign commit, set signing_key or git user.signingkey: {}",
+                    String::from_utf8_lossy(&output.stderr),
+                ));
+            }
+
             bail!(format!("failed to commit changes: {}", String::from_utf8_lossy(&output.stderr)));
         }
 
         Ok(())
     }
 
-    // push commit
-    pub fn push(&self) -> Result<()> {
+    // commit a page to the repository wiki and return its url
+    pub fn publish_wiki_page(&self, page: &str, content: &str) -> Result<String> {
+        let wiki_url = format!("{}.wiki.git", self.repo_url.trim_end_matches(".git"));
+        let wiki_dir = std::env::temp_dir().join(format!("donder-release-wiki-{}", page));
+        let wiki_path = wiki_dir.to_string_lossy().to_string();
+
+        // Start from a fresh clone
+        std::fs::remove_dir_all(&wiki_dir).ok();
+
+        let output = self.remote_command()
+            .args(["clone", "--depth=1", &wiki_url, &wiki_path])
+            .output()?;
+
+        if !output.status.success() {
+            bail!("failed to clone wiki, make sure it has at least one page: {}", String::from_utf8_lossy(&output.stderr));
+        }
+
+        std::fs::write(wiki_dir.join(format!("{}.md", page)), content)
+            .context("failed to write wiki page")?;
+
         let output = Command::new("git")
-            .args(["push", &format!("--repo={}", &self.repo_url.as_str())])
+            .args(["-C", &wiki_path, "add", "--all"])
+            .output()?;
+
+        if !output.status.success() {
+            bail!("failed to add wiki page");
+        }
+
+        let output = Command::new("git")
+            .args([
+                "-C", &wiki_path,
+                "-c", &format!("user.name={}", self.author),
+                "-c", &format!("user.email={}", self.email),
+                "commit", "-m", &format!("Release notes {}", page),
+            ])
+            .output()?;
+
+        if !output.status.success() {
+            bail!("failed to commit wiki page: {}", String::from_utf8_lossy(&output.stderr));
+        }
+
+        let output = self.remote_command()
+            .args(["-C", &wiki_path, "push", &wiki_url])
+            .output()?;
+
+        if !output.status.success() {
+            bail!("failed to push wiki page: {}", String::from_utf8_lossy(&output.stderr));
+        }
+
+        std::fs::remove_dir_all(&wiki_dir).ok();
+
+        Ok(format!("{}/wiki/{}", self.origin_url()?, page))
+    }
+
+    // push commit to the given branch, the current branch when empty
+    pub fn push(&self, branch: &str) -> Result<()> {
+        let branch = match branch.is_empty() {
+            true => self.current_branch()?,
+            false => branch.to_string(),
+        };
+
+        if branch == "HEAD" {
+            self.undo_commit()?;
+            bail!("HEAD is detached, set the branch option to the branch releases are pushed to");
+        }
+
+        let output = self.remote_command()
+            .args(["push", self.repo_url.as_str(), &format!("HEAD:{}", branch)])
             .output()?;
 
         // check if push was successful
@@ -205,7 +577,7 @@ impl Git {
 
     // push tag
     pub fn push_tag(&self, tag: &str) -> Result<()> {
-        let output = Command::new("git")
+        let output = self.remote_command()
             .args(["push", &self.repo_url.as_str(), tag])
             .output()?;
 
@@ -218,9 +590,22 @@ impl Git {
         Ok(())
     }
 
+    // check if tag exists on remote
+    pub fn remote_tag_exists(&self, tag: &str) -> Result<bool> {
+        let output = self.remote_command()
+            .args(["ls-remote", "--tags", self.repo_url.as_str(), &format!("refs/tags/{}", tag)])
+            .output()?;
+
+        if !output.status.success() {
+            bail!(format!("failed to list remote tags: {}", String::from_utf8_lossy(&output.stderr)));
+        }
+
+        Ok(!String::from_utf8_lossy(&output.stdout).trim().is_empty())
+    }
+
     // delete tag on remote
     pub fn delete_tag(&self, tag: &str) -> Result<()> {
-        let output = Command::new("git")
+        let output = self.remote_command()
             .args(["push", "--delete", &self.repo_url.as_str(), tag])
             .output()?;
 
@@ -245,6 +630,47 @@ impl Git {
         Ok(())
     }
 
+    // undo uncommitted changes of files, files that are not tracked are removed when remove_untracked is set
+    pub fn undo_changes(&self, paths: &[String], remove_untracked: bool) -> Result<()> {
+        for path in paths {
+            let tracked = Command::new("git")
+                .args(["ls-files", "--error-unmatch", path])
+                .output()?
+                .status
+                .success();
+
+            if !tracked {
+                if remove_untracked {
+                    std::fs::remove_file(path).ok();
+                }
+                continue;
+            }
+
+            let output = Command::new("git")
+                .args(["checkout", "HEAD", "--", path])
+                .output()?;
+
+            if !output.status.success() {
+                bail!(format!("failed to undo changes of {}: {}", path, String::from_utf8_lossy(&output.stderr)));
+            }
+        }
+
+        Ok(())
+    }
+
+    // undo last commit keeping its changes in the working tree
+    pub fn uncommit(&self) -> Result<()> {
+        let output = Command::new("git")
+            .args(["reset", "HEAD^"])
+            .output()?;
+
+        if !output.status.success() {
+            bail!("failed to undo commit");
+        }
+
+        Ok(())
+    }
+
     // undo last commit and changes
     pub fn undo_commit(&self) -> Result<()> {
         let output = Command::new("git")
@@ -259,6 +685,23 @@ impl Git {
     }
 }
 
+/// Parses the output of git log formatted as `%h%x00%H%x00%an%x00%s%x00%b%x1e` into commits.
+fn parse_commits(output: &str) -> Commits {
+    output
+        .split('\x1e')
+        .map(|record| record.trim_start_matches('\n'))
+        .filter(|record| !record.is_empty())
+        .map(|record| {
+            let commit = record.split('\0').collect::<Vec<&str>>();
+            match commit.len() {
+                5 => Commit::new(commit[0], commit[1], commit[3], commit[4].trim_end(), commit[2]),
+                4 => Commit::new(commit[0], commit[1], commit[3], "", commit[2]),
+                _ => Commit::new("", "", "", "", ""),
+            }
+        })
+        .collect()
+}
+
 #[derive(Debug)]
 pub struct ReleaseInfo {
     pub version: Version,
@@ -270,7 +713,7 @@ pub struct ReleaseInfo {
 impl ReleaseInfo {
     pub fn new(tag: &str, prefix: &str, initial: bool) -> Self {
         Self {
-            version: Version::parse(&tag.replace(&prefix, "")).unwrap(),
+            version: Version::parse(tag.strip_prefix(prefix).unwrap_or(tag)).unwrap(),
             prefix: prefix.to_string(),
             head: "".to_string(),
             initial,
@@ -293,14 +736,73 @@ pub struct Commit {
     pub subject: String,
     pub body: String,
     pub hash: String,
+    pub full_hash: String,
+    pub author: String,
 }
 
 impl Commit {
-    pub fn new(hash: &str, subject: &str, body: &str) -> Self {
+    pub fn new(hash: &str, full_hash: &str, subject: &str, body: &str, author: &str) -> Self {
         Self {
             subject: subject.to_string(),
             body: body.to_string(),
             hash: hash.to_string(),
+            full_hash: full_hash.to_string(),
+            author: author.to_string(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commits_with_multi_line_bodies_are_parsed() {
+        let output = "abc1234\0abc1234f\0Jane Doe\0feat: add endpoint\0Adds the endpoint.\n\nRefs: #12\n\x1e\n\
+                      def5678\0def5678f\0John Doe\0fix: handle empty configs\0\x1e";
+
+        let commits = parse_commits(output);
+
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].hash, "abc1234");
+        assert_eq!(commits[0].full_hash, "abc1234f");
+        assert_eq!(commits[0].author, "Jane Doe");
+        assert_eq!(commits[0].subject, "feat: add endpoint");
+        assert_eq!(commits[0].body, "Adds the endpoint.\n\nRefs: #12");
+        assert_eq!(commits[1].subject, "fix: handle empty configs");
+        assert_eq!(commits[1].body, "");
+    }
+
+    #[test]
+    fn tag_prefix_is_only_stripped_from_the_start() {
+        let release = ReleaseInfo::new("v1.2.3+build-v2", "v", false);
+
+        assert_eq!(release.version.to_string(), "1.2.3+build-v2");
+        assert_eq!(release.version.build.as_str(), "build-v2");
+        assert_eq!(release.tag(), "v1.2.3+build-v2");
+    }
+
+    #[test]
+    fn existing_remote_tag_is_found() {
+        let remote = std::env::temp_dir().join(format!("donder-release-remote-{}", std::process::id()));
+        let remote_url = remote.to_string_lossy().to_string();
+        let run = |args: &[&str]| {
+            let status = Command::new("git").args(["-C", &remote_url]).args(args).status().unwrap();
+            assert!(status.success(), "git {:?}", args);
+        };
+
+        std::fs::create_dir_all(&remote).unwrap();
+        run(&["init", "-q"]);
+        run(&["-c", "user.name=Jane Doe", "-c", "user.email=jane@example.com", "commit", "-q", "--allow-empty", "-m", "feat: init"]);
+        run(&["tag", "v1.2.4"]);
+
+        let git = Git { repo_url: remote_url.clone(), ..Default::default() };
+
+        let exists = git.remote_tag_exists("v1.2.4").unwrap();
+        let missing = git.remote_tag_exists("v1.2.5").unwrap();
+        std::fs::remove_dir_all(&remote).unwrap();
+
+        assert!(exists);
+        assert!(!missing);
+    }
+}
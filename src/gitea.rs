@@ -0,0 +1,70 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::api::{GithubApi, ReleaseProvider};
+
+/// Gitea (and Forgejo) serve a GitHub compatible release API under /api/v1
+#[derive(Default, Debug)]
+pub struct GiteaApi {
+    api: GithubApi,
+}
+
+impl GiteaApi {
+    pub fn new(token: &str, owner: &str, repo: &str, api_url: &str, host: &str) -> Self {
+        let base_url = match api_url.is_empty() {
+            true => format!("https://{}/api/v1", host),
+            false => api_url.to_string(),
+        };
+
+        Self {
+            api: GithubApi::new(token, owner, repo, &base_url, host)
+                .with_authorization(format!("token {}", token)),
+        }
+    }
+
+    /// Retries requests that failed on the server side up to this number of times
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.api = self.api.with_max_retries(max_retries);
+        self
+    }
+}
+
+#[async_trait]
+impl ReleaseProvider for GiteaApi {
+    async fn check_repo_access(&self) -> Result<()> {
+        self.api.check_repo_access().await
+    }
+
+    async fn check_push_access(&self) -> Result<()> {
+        self.api.check_push_access().await
+    }
+
+    async fn create_release(
+        &self,
+        release_tag: &str,
+        release_notes: &str,
+        prerelease: bool,
+        target_commitish: &str,
+    ) -> Result<()> {
+        self.api.create_release(release_tag, release_notes, prerelease, target_commitish).await
+    }
+
+    async fn find_release(&self, release_tag: &str) -> Result<Option<String>> {
+        self.api.find_release(release_tag).await
+    }
+
+    async fn update_release(
+        &self,
+        release_id: &str,
+        release_tag: &str,
+        release_notes: &str,
+        prerelease: bool,
+        target_commitish: &str,
+    ) -> Result<()> {
+        self.api.update_release(release_id, release_tag, release_notes, prerelease, target_commitish).await
+    }
+
+    async fn clean_pre_releases(&self, tag_prefix: &str) -> Result<()> {
+        self.api.clean_pre_releases(tag_prefix).await
+    }
+}
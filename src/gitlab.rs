@@ -0,0 +1,260 @@
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde::{Serialize, Deserialize};
+use semver::Version;
+
+use crate::api::{ReleaseProvider, SendWithRetry};
+
+#[derive(Default, Debug)]
+pub struct GitLabApi {
+    /// The path to the project (e.g: https://gitlab.com/api/v4/projects/owner%2Frepo)
+    pub api_url: String,
+
+    // to be used in request headers
+    private_token: String,
+    /// Number of times requests that failed on the server side (5xx or 429) are retried
+    max_retries: u32,
+}
+
+#[derive(Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+}
+
+#[derive(Deserialize)]
+pub struct Project {
+    pub permissions: Option<ProjectPermissions>,
+}
+
+#[derive(Deserialize)]
+pub struct ProjectPermissions {
+    pub project_access: Option<AccessLevel>,
+    pub group_access: Option<AccessLevel>,
+}
+
+#[derive(Deserialize)]
+pub struct AccessLevel {
+    pub access_level: u64,
+}
+
+/// Lowest access level that can push to a project (developer)
+const DEVELOPER_ACCESS: u64 = 30;
+
+impl GitLabApi {
+    pub fn new(token: &str, owner: &str, repo: &str, api_url: &str, host: &str) -> Self {
+        let base_url = match api_url.is_empty() {
+            true => format!("https://{}/api/v4", host),
+            false => api_url.to_string(),
+        };
+
+        Self {
+            api_url: format!("{}/projects/{}%2F{}", base_url.trim_end_matches("/"), owner, repo),
+            private_token: token.to_string(),
+            max_retries: 0,
+        }
+    }
+
+    /// Retries requests that failed on the server side up to this number of times
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    async fn release_exists(&self, release_tag: &str) -> Result<bool> {
+        let client = reqwest::Client::new();
+        let response = client
+            .get(format!("{}/releases/{}", &self.api_url, release_tag.replace("/", "%2F")))
+            .header("PRIVATE-TOKEN", &self.private_token)
+            .send_with_retry(self.max_retries)
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+
+        if !response.status().is_success() {
+            // get error message from response
+            let error_message = response.text().await?;
+            println!("error: {}", error_message);
+            bail!(error_message);
+        }
+
+        Ok(true)
+    }
+
+    async fn get_project(&self) -> Result<Project> {
+        let client = reqwest::Client::new();
+        let response = client
+            .get(&self.api_url)
+            .header("PRIVATE-TOKEN", &self.private_token)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            // get error message from response
+            let error_message = response.text().await?;
+            println!("error: {}", error_message);
+            bail!(error_message);
+        }
+
+        Ok(response.json().await?)
+    }
+}
+
+#[async_trait]
+impl ReleaseProvider for GitLabApi {
+    async fn check_repo_access(&self) -> Result<()> {
+        self.get_project().await?;
+
+        Ok(())
+    }
+
+    async fn check_push_access(&self) -> Result<()> {
+        let project = self.get_project().await?;
+
+        let access_level = project.permissions
+            .map(|p| {
+                p.project_access.map_or(0, |a| a.access_level)
+                    .max(p.group_access.map_or(0, |a| a.access_level))
+            })
+            .unwrap_or(0);
+
+        if access_level < DEVELOPER_ACCESS {
+            bail!("token does not have write access to the project");
+        }
+
+        Ok(())
+    }
+
+    // GitLab has no pre release flag, pre releases are plain releases of their tag. The target is only used by GitLab
+    // to create a missing tag, release tags are always pushed first.
+    async fn create_release(
+        &self,
+        release_tag: &str,
+        release_notes: &str,
+        _prerelease: bool,
+        _target_commitish: &str,
+    ) -> Result<()> {
+        let request_body = PostRelease {
+            tag_name: release_tag.to_string(),
+            name: release_tag.to_string(),
+            description: release_notes.to_string(),
+        };
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}/releases", &self.api_url))
+            .header("PRIVATE-TOKEN", &self.private_token)
+            .json(&request_body)
+            .send_with_retry(self.max_retries)
+            .await?;
+
+        if !response.status().is_success() {
+            // get error message from response
+            let error_message = response.text().await?;
+            println!("error: {}", error_message);
+            bail!(error_message);
+        }
+
+        Ok(())
+    }
+
+    // Releases are identified by their tag
+    async fn find_release(&self, release_tag: &str) -> Result<Option<String>> {
+        match self.release_exists(release_tag).await? {
+            true => Ok(Some(release_tag.to_string())),
+            false => Ok(None),
+        }
+    }
+
+    // Deleting a tag deletes its release, the release is created again once its tag is recreated
+    async fn update_release(
+        &self,
+        release_id: &str,
+        release_tag: &str,
+        release_notes: &str,
+        prerelease: bool,
+        target_commitish: &str,
+    ) -> Result<()> {
+        if !self.release_exists(release_id).await? {
+            return self.create_release(release_tag, release_notes, prerelease, target_commitish).await;
+        }
+
+        let request_body = PostRelease {
+            tag_name: release_tag.to_string(),
+            name: release_tag.to_string(),
+            description: release_notes.to_string(),
+        };
+
+        let client = reqwest::Client::new();
+        let response = client
+            .put(format!("{}/releases/{}", &self.api_url, release_id.replace("/", "%2F")))
+            .header("PRIVATE-TOKEN", &self.private_token)
+            .json(&request_body)
+            .send_with_retry(self.max_retries)
+            .await?;
+
+        if !response.status().is_success() {
+            // get error message from response
+            let error_message = response.text().await?;
+            println!("error: {}", error_message);
+            bail!(error_message);
+        }
+
+        Ok(())
+    }
+
+    fn release_url(&self, origin_url: &str, release_tag: &str) -> String {
+        format!("{}/-/releases/{}", origin_url, release_tag)
+    }
+
+    async fn clean_pre_releases(&self, tag_prefix: &str) -> Result<()> {
+        let client = reqwest::Client::new();
+        let response = client
+            .get(format!("{}/releases", &self.api_url))
+            .header("PRIVATE-TOKEN", &self.private_token)
+            .send_with_retry(self.max_retries)
+            .await?;
+
+        if !response.status().is_success() {
+            // get error message from response
+            let error_message = response.text().await?;
+            println!("error: {}", error_message);
+            bail!(error_message);
+        }
+
+        let releases: Vec<Release> = response.json().await?;
+
+        for release in releases {
+            // Skip releases that belong to other tag prefixes (e.g: other packages)
+            let version = match release.tag_name.strip_prefix(tag_prefix).map(Version::parse) {
+                Some(Ok(version)) => version,
+                _ => continue,
+            };
+            if !version.pre.is_empty() {
+                let response = client
+                    .delete(format!("{}/releases/{}", &self.api_url, release.tag_name.replace("/", "%2F")))
+                    .header("PRIVATE-TOKEN", &self.private_token)
+                    .send_with_retry(self.max_retries)
+                    .await?;
+
+                if !response.status().is_success() {
+                    // get error message from response
+                    let error_message = response.text().await?;
+                    println!("error: {}", error_message);
+                    bail!(error_message);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct PostRelease {
+    tag_name: String,
+    name: String,
+    description: String,
+}
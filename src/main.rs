@@ -1,12 +1,28 @@
-use std::process;
+use std::{io::{self, BufRead, IsTerminal, Write}, process, sync::atomic::{AtomicBool, Ordering}};
 use clap::Parser;
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
 use chrono::Local;
 
+/// Logs go to stderr when stdout is reserved for machine readable output
+static LOG_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
 macro_rules! logInfo {
     ($($arg:tt)*) => ({
         let time = Local::now().format("[%T%.3f]");
-        println!("{} Info: {}", time, format_args!($($arg)*));
+        match crate::LOG_TO_STDERR.load(std::sync::atomic::Ordering::Relaxed) {
+            true => eprintln!("{} Info: {}", time, format_args!($($arg)*)),
+            false => println!("{} Info: {}", time, format_args!($($arg)*)),
+        }
+    })
+}
+
+macro_rules! logWarn {
+    ($($arg:tt)*) => ({
+        let time = Local::now().format("[%T%.3f]");
+        match crate::LOG_TO_STDERR.load(std::sync::atomic::Ordering::Relaxed) {
+            true => eprintln!("{} Warning: {}", time, format_args!($($arg)*)),
+            false => println!("{} Warning: {}", time, format_args!($($arg)*)),
+        }
     })
 }
 
@@ -24,8 +40,13 @@ mod api;
 mod changelog;
 mod bump_files;
 mod package;
+mod gitlab;
+mod gitea;
+mod manifest;
+mod actions;
 
 use ctx::Ctx;
+use package::Pkg;
 
 /// donder-release CLI
 /// - Quickly create releases on Github from the command line or CI using conventional commits.
@@ -46,6 +67,67 @@ struct Cli {
     /// Preview a pending release without publishing it
     #[arg(long, default_value = "false")]
     dry_run: bool,
+    /// Make a pending release locally and validate GitHub API access without pushing or publishing it
+    #[arg(long, default_value = "false")]
+    dry_run_publish: bool,
+    /// Compute the next release from local history without any network access: skips fetching tags from the
+    /// remote and validating API access, and previews the release without publishing it (implies --dry-run)
+    #[arg(long, default_value = "false", conflicts_with = "dry_run_publish")]
+    offline: bool,
+    /// Print the next release version of every package, one per line, without releasing and exit with an error when
+    /// there is nothing to release, logs are written to stderr (implies --dry-run)
+    #[arg(long, default_value = "false", conflicts_with_all = ["dry_run_publish", "clean_prereleases"])]
+    print_version: bool,
+    /// Clean pre releases of the selected packages without releasing, respects --dry-run by listing them
+    #[arg(long, default_value = "false", alias = "clean-pre-releases")]
+    clean_prereleases: bool,
+    /// Publish without asking for confirmation, the prompt is also skipped when stdin is not a terminal (e.g: CI)
+    #[arg(long, short, default_value = "false")]
+    yes: bool,
+    /// Recreate the release tag when it already exists on the remote
+    #[arg(long, default_value = "false")]
+    force: bool,
+    /// Write the commits that comprise each release, with their full hashes and subjects, to this JSON file
+    #[arg(long, default_value = "")]
+    commits_out: String,
+    /// Write the release notes of every release to this file
+    #[arg(long, default_value = "")]
+    notes_file: String,
+    /// Format of the notes written to --notes-file, GitHub releases are always markdown
+    #[arg(long, default_value = "markdown", value_parser = ["markdown", "html"])]
+    notes_format: String,
+    /// Format of the release notes printed by --dry-run, json prints the changelog as structured data
+    #[arg(long, default_value = "markdown", value_parser = ["markdown", "json"])]
+    format: String,
+    /// Print a JSON summary of every package (name, previous_version, next_version, released, release_url and
+    /// changelog) once done, logs are written to stderr
+    #[arg(long, default_value = "text", value_parser = ["text", "json"])]
+    output: String,
+    /// Sign the release commit and tag with this GPG key ID instead of the configured one
+    #[arg(long, default_value = "")]
+    signing_key: String,
+    /// Load and validate the configuration file and bump files, print the problems found and exit
+    #[arg(long, default_value = "false")]
+    check_config: bool,
+    /// Explain how the next release version is computed
+    #[arg(long, default_value = "false")]
+    explain: bool,
+    /// Fail when a commit in range does not follow the conventional commits format
+    #[arg(long, default_value = "false")]
+    strict: bool,
+    /// Release this version instead of the one computed from commits (e.g: 2.0.0), it must be greater than the last
+    /// release
+    #[arg(long, default_value = "")]
+    set_version: String,
+    /// Release a version above the configured max_version
+    #[arg(long, default_value = "false")]
+    allow_exceed_ceiling: bool,
+    /// Only include the commits of this pull request, it must target the current branch
+    #[arg(long, default_value = "0", conflicts_with = "offline")]
+    pr: u64,
+    /// Keep releasing the remaining packages when one fails and report a summary, exits with an error if any failed
+    #[arg(long, default_value = "false")]
+    continue_on_error: bool,
     /// Outputs the CLI version
     #[arg(long, short, default_value = "false")]
     version: bool,
@@ -55,6 +137,11 @@ struct Cli {
 async fn main() -> Result<()> {
     let args = Cli::parse();
 
+    // Keep stdout clean for the JSON summary, the JSON notes or the printed versions
+    if args.output == "json" || args.format == "json" || args.print_version {
+        LOG_TO_STDERR.store(true, Ordering::Relaxed);
+    }
+
     if args.version {
         println!("{}", env!("CARGO_PKG_VERSION"));
         return Ok(());
@@ -72,100 +159,608 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
+    // Validate configuration only
+    if args.check_config {
+        let mut problems = match Ctx::load(&args.config) {
+            Ok(config) => config.problems(&args.pre_id, false),
+            Err(e) => vec![format!("{:#}", e)],
+        };
+
+        // Packages and their bump files are only checked once the config itself is valid
+        if problems.is_empty() {
+            problems = match Ctx::new(args.config, args.pre_id, true, false, args.packages, false) {
+                Ok(ctx) => ctx.packages
+                    .iter()
+                    .filter_map(|pkg| pkg.check_bump_files(&ctx).err().map(|e| match pkg.name.is_empty() {
+                        true => format!("{:#}", e),
+                        false => format!("{}: {:#}", pkg.name, e),
+                    }))
+                    .collect::<Vec<String>>(),
+                Err(e) => vec![format!("{:#}", e)],
+            };
+        }
+
+        if !problems.is_empty() {
+            for problem in problems {
+                logError!("{}", problem);
+            }
+            process::exit(1);
+        }
+
+        println!("config OK");
+
+        return Ok(());
+    }
+
     // Load configuration file into context
-    let ctx = Ctx::new(args.config, args.pre_id, args.dry_run, args.packages)
+    let mut ctx = Ctx::new(
+        args.config,
+        args.pre_id,
+        args.dry_run || args.offline || args.print_version,
+        args.dry_run_publish,
+        args.packages,
+        args.print_version,
+    )
         .unwrap_or_else(|e| {
-            logError!("Loading configuration - {}", e.to_string());
+            logError!("Loading configuration - {:#}", e);
+            process::exit(1);
+        });
+    ctx.explain = args.explain;
+    ctx.format = args.format;
+    ctx.output = args.output.clone();
+    ctx.offline = args.offline;
+    ctx.force = args.force;
+    ctx.yes = args.yes;
+    ctx.strict = args.strict;
+    ctx.allow_exceed_ceiling = args.allow_exceed_ceiling;
+
+    // Validate the explicit version before touching the repo
+    if !args.set_version.is_empty() {
+        semver::Version::parse(&args.set_version).unwrap_or_else(|e| {
+            logError!("Parsing set version - {}", e.to_string());
             process::exit(1);
         });
 
+        ctx.set_version = args.set_version.clone();
+    }
+
+    // Release commits and tags are signed with the selected key, the CLI key takes precedence
+    let signing_key = match args.signing_key.is_empty() {
+        true => ctx.signing_key.clone(),
+        false => args.signing_key.clone(),
+    };
+
+    if !signing_key.is_empty() {
+        ctx.git.check_signing_key(&signing_key).unwrap_or_else(|e| {
+            logError!("Validating signing key - {}", e.to_string());
+            process::exit(1);
+        });
+
+        ctx.git.signing_key = signing_key;
+    }
+
+    // Only tags of the released packages are fetched, a package without prefix needs all tags
+    let mut tag_prefixes = match ctx.fetch_prefix_tags {
+        true => ctx.packages.iter().map(|pkg| pkg.tag_prefix.clone()).collect::<Vec<String>>(),
+        false => vec![],
+    };
+    if tag_prefixes.iter().any(|prefix| prefix.is_empty()) {
+        tag_prefixes.clear();
+    } else if ctx.combined_release && !tag_prefixes.is_empty() {
+        tag_prefixes.push("release-".to_string());
+    }
+
     // Sync local git repo with remote
-    ctx.git.sync().unwrap_or_else(|e| {
+    ctx.git.sync(ctx.offline, &tag_prefixes, &ctx.branch).unwrap_or_else(|e| {
         logError!("Syncing git repo - {}", e.to_string());
         process::exit(1);
     });
 
     // Log mode
+    if ctx.offline {
+        logInfo!("Running offline, only local history and tags are used");
+    }
+
     match ctx.preview {
         true => logInfo!("Running in preview mode, release will not be published"),
+        false if ctx.dry_run_publish => logInfo!("Running in dry run publish mode, release will not be published"),
         false => logInfo!("Running in publish mode, release will be published"),
     }
 
-    for mut pkg in ctx.packages {
-        if !pkg.name.is_empty() {
-            logInfo!("Processing package {}", pkg.name);
+    // Validate write access before any git mutation
+    if !ctx.preview {
+        ctx.releases.check_push_access()
+            .await
+            .unwrap_or_else(|e| {
+                logError!("Validating GitHub API access - {}", e.to_string());
+                process::exit(1);
+            });
+    }
+
+    // Validate GitHub API access without publishing
+    if ctx.dry_run_publish {
+        logInfo!("Validating GitHub API access");
+
+        ctx.releases.check_repo_access()
+            .await
+            .unwrap_or_else(|e| {
+                logError!("Validating GitHub API access - {}", e.to_string());
+                process::exit(1);
+            });
+
+        logInfo!("GitHub API access validated");
+    }
+
+    // Use the commit range of a pull request
+    if args.pr > 0 {
+        if ctx.provider != "github" {
+            logError!("--pr is only supported by the github provider");
+            process::exit(1);
         }
 
-        // Get last release info
-        pkg.last_release(&ctx.git, &ctx.pre_id).unwrap_or_else(|e| {
-            logError!("Getting last release - {}", e.to_string());
+        let pull_request = ctx.api.get_pull_request(args.pr)
+            .await
+            .unwrap_or_else(|e| {
+                logError!("Getting pull request {} - {}", args.pr, e.to_string());
+                process::exit(1);
+            });
+
+        // The release branch is the checked out branch unless configured
+        let release_branch = match ctx.branch.is_empty() {
+            true => ctx.git.current_branch().unwrap_or_else(|e| {
+                logError!("Getting current branch - {}", e.to_string());
+                process::exit(1);
+            }),
+            false => ctx.branch.clone(),
+        };
+
+        if pull_request.base.git_ref != release_branch {
+            logError!(
+                "Pull request {} targets {} instead of the release branch {}",
+                args.pr,
+                pull_request.base.git_ref,
+                release_branch,
+            );
             process::exit(1);
-        });
+        }
 
-        // Get commits
-        pkg.get_commits(&ctx.git).unwrap_or_else(|e| {
-            logError!("Getting commits - {}", e.to_string());
+        ctx.git.fetch_pull_request(args.pr).unwrap_or_else(|e| {
+            logError!("Fetching pull request - {}", e.to_string());
             process::exit(1);
         });
 
-        
-        // Generate changelog
-        let has_changelog = pkg.load_changelog(&ctx.pre_id, &ctx.types)
-            .unwrap_or_else(|e| {
-                logError!("Generating changelog - {}", e.to_string());
+        ctx.pr_range = format!("{}..{}", pull_request.base.sha, pull_request.head.sha);
+    }
+
+    // Print next versions only
+    if args.print_version {
+        let mut has_release = false;
+        let mut released: Vec<package::DependencyUpdate> = vec![];
+
+        for mut pkg in std::mem::take(&mut ctx.packages) {
+            if !pkg.name.is_empty() {
+                logInfo!("Processing package {}", pkg.name);
+            }
+
+            pkg.dependency_updates = released
+                .iter()
+                .filter(|update| pkg.depends_on.contains(&update.package))
+                .cloned()
+                .collect();
+
+            let version = next_version(&mut pkg, &ctx).unwrap_or_else(|e| {
+                logError!("Computing next version - {}", e.to_string());
                 process::exit(1);
             });
 
-        if has_changelog {
-            // Write release notes
-            pkg.write_notes(&ctx.preview, &ctx.git, &ctx.types, &ctx.changelog_file)
+            if let Some(version) = version {
+                match pkg.name.is_empty() {
+                    true => println!("{}", version),
+                    false => println!("{} {}", pkg.name, version),
+                }
+                has_release = true;
+                released.push(pkg.dependency_update());
+            }
+        }
+
+        if !has_release {
+            process::exit(1);
+        }
+
+        return Ok(());
+    }
+
+    // Clean pre releases only
+    if args.clean_prereleases {
+        for pkg in &ctx.packages {
+            if !pkg.name.is_empty() {
+                logInfo!("Processing package {}", pkg.name);
+            }
+
+            pkg.clean_pre_releases(&ctx.git, ctx.releases.as_ref(), &ctx.preview)
+                .await
                 .unwrap_or_else(|e| {
-                    logError!("Writing release notes - {}", e.to_string());
+                    logError!("Cleaning pre releases - {}", e.to_string());
                     process::exit(1);
                 });
-        
-            // Publish or preview release
-            match ctx.preview {
-                true => {
-                    logInfo!("Previewing release");
-                    
-                    for line in pkg.changelog.notes.lines() {
-                        println!("{}", line);
-                    }
-
-                    println!();
-                },
-                false => {
-                    // Bump files
-                    pkg.bump_files()
-                        .unwrap_or_else(|e| {
-                            logError!("Bumping files - {}", e.to_string());
-                            process::exit(1);
-                        });
-        
-                    // Publish release
-                    pkg.publish_release(&ctx.git, &ctx.api, &ctx.release_message)
-                        .await
-                        .unwrap_or_else(|e| {
-                            logError!("Publishing release - {}", e.to_string());
-                            process::exit(1);
-                        });
-
-                    // Clean pre releases
-                    if ctx.pre_id.is_empty() && ctx.clean_pre_releases {
-                        pkg.clean_pre_releases(&ctx.git, &ctx.api)
-                            .await
-                            .unwrap_or_else(|e| {
-                                logInfo!("Cleaning pre releases had some issues - {}", e.to_string());
-                            });
-                        
-                    }
+        }
+
+        logInfo!("Completed successfully 🎉");
+
+        return Ok(());
+    }
+
+    // Notes of every released package when publishing a combined release
+    let mut combined_notes: Vec<String> = vec![];
+    // Release commit of the last released package, the combined release is attached to it
+    let mut combined_target = String::new();
+    // Commits of every release keyed by package name
+    let mut commits_out = serde_json::Map::new();
+    // Notes of every release written to --notes-file
+    let mut notes_out: Vec<String> = vec![];
+
+    let packages = std::mem::take(&mut ctx.packages);
+
+    // Packages processed in this run, failures are collected with --continue-on-error
+    let mut succeeded: Vec<String> = vec![];
+    let mut failed: Vec<String> = vec![];
+    // Summary of every package printed with --output json
+    let mut summaries: Vec<serde_json::Value> = vec![];
+    // Notes of every previewed release printed with --format json
+    let mut notes_json: Vec<serde_json::Value> = vec![];
+    // Releases of this run, packages are processed after their dependencies
+    let mut released: Vec<package::DependencyUpdate> = vec![];
+
+    for mut pkg in packages {
+        if !pkg.name.is_empty() {
+            logInfo!("Processing package {}", pkg.name);
+        }
+
+        pkg.dependency_updates = released
+            .iter()
+            .filter(|update| pkg.depends_on.contains(&update.package))
+            .cloned()
+            .collect();
+
+        let label = match pkg.name.is_empty() {
+            true => "root".to_string(),
+            false => pkg.name.clone(),
+        };
+
+        let has_changelog = match release_package(
+            &mut pkg,
+            &ctx,
+            !args.commits_out.is_empty(),
+            &mut commits_out,
+            &mut combined_notes,
+        ).await {
+            Ok(has_changelog) => has_changelog,
+            Err(e) => {
+                logError!("{:#}", e);
+
+                if !args.continue_on_error {
+                    process::exit(1);
                 }
+
+                failed.push(label);
+                continue;
+            }
+        };
+
+        succeeded.push(label);
+
+        if has_changelog {
+            released.push(pkg.dependency_update());
+        }
+
+        if has_changelog && ctx.combined_release && !ctx.preview && !ctx.dry_run_publish {
+            combined_target = ctx.git.tag_head(&pkg.changelog.next_release_version).unwrap_or_default();
+        }
+
+        if has_changelog && !args.notes_file.is_empty() {
+            notes_out.push(pkg.changelog.notes.clone());
+        }
+
+        if args.output == "json" {
+            summaries.push(pkg.summary(has_changelog, has_changelog && !ctx.preview && !ctx.dry_run_publish));
+        } else if ctx.format == "json" && has_changelog && ctx.preview {
+            match pkg.notes_json(&ctx) {
+                Ok(notes) => notes_json.push(notes),
+                Err(e) => logWarn!("Rendering JSON notes - {}", e.to_string()),
             }
         }
+
+        // Write GitHub Actions step outputs, does nothing when $GITHUB_OUTPUT is not defined
+        pkg.write_outputs(has_changelog, has_changelog && !ctx.preview && !ctx.dry_run_publish)
+            .unwrap_or_else(|e| {
+                logWarn!("Writing GitHub Actions outputs - {}", e.to_string());
+            });
+    }
+
+    if args.output == "json" {
+        println!("{}", serde_json::to_string_pretty(&summaries).unwrap());
+    } else if ctx.format == "json" && ctx.preview {
+        println!("{}", serde_json::to_string_pretty(&notes_json).unwrap());
+    }
+
+    // Write commits of every release
+    if !args.commits_out.is_empty() {
+        let content = serde_json::to_string_pretty(&commits_out).unwrap();
+
+        std::fs::write(&args.commits_out, content)
+            .unwrap_or_else(|e| {
+                logError!("Writing commits to {} - {}", args.commits_out, e.to_string());
+                process::exit(1);
+            });
+
+        logInfo!("Wrote release commits to {}", args.commits_out);
+    }
+
+    // Write notes of every release
+    if !args.notes_file.is_empty() {
+        let notes = notes_out.join("\r\n");
+        let content = match args.notes_format.as_str() {
+            "html" => changelog::notes_html(&notes),
+            _ => notes,
+        };
+
+        std::fs::write(&args.notes_file, content)
+            .unwrap_or_else(|e| {
+                logError!("Writing notes to {} - {}", args.notes_file, e.to_string());
+                process::exit(1);
+            });
+
+        logInfo!("Wrote release notes to {}", args.notes_file);
+    }
+
+    // Publish a single release for all packages
+    if !ctx.preview && ctx.combined_release && !combined_notes.is_empty() {
+        logInfo!("Publishing combined release");
+
+        let tag = ctx.git.combined_release_tag().unwrap_or_else(|e| {
+            logError!("Getting combined release tag - {}", e.to_string());
+            process::exit(1);
+        });
+
+        // Release tag
+        ctx.git.tag(&tag, &ctx.tag_message(&tag, &combined_notes.join("\r\n")))
+            .and_then(|_| ctx.git.push_tag(&tag))
+            .unwrap_or_else(|e| {
+                logError!("Tagging combined release - {}", e.to_string());
+                process::exit(1);
+            });
+
+        // Create release on GitHub attached to the release commit, a pre release when releasing with a pre id
+        ctx.releases.create_release(&tag, &combined_notes.join("\r\n"), !ctx.pre_id.is_empty(), &combined_target)
+            .await
+            .unwrap_or_else(|e| {
+                logError!("Publishing combined release - {}", e.to_string());
+                process::exit(1);
+            });
+    }
+
+    if ctx.dry_run_publish {
+        logInfo!("Dry run publish, no release was created");
+    }
+
+    // Summary of a run that continued past failed packages
+    if !failed.is_empty() {
+        if !succeeded.is_empty() {
+            logInfo!("Succeeded packages: {}", succeeded.join(", "));
+        }
+        logError!("Failed packages: {}", failed.join(", "));
+        process::exit(1);
     }
 
     logInfo!("Completed successfully 🎉");
 
     Ok(())
 }
+
+/// Computes the next release version of a package from its commits, returns none when there is nothing to release
+fn next_version(pkg: &mut Pkg, ctx: &Ctx) -> Result<Option<String>> {
+    pkg.last_release(ctx).context("Getting last release")?;
+    pkg.get_commits(ctx).context("Getting commits")?;
+
+    if ctx.strict_path_attribution {
+        pkg.attribute_commits(&ctx.git).context("Attributing commits")?;
+    }
+
+    match pkg.load_changelog(ctx).context("Generating changelog")? {
+        true => Ok(Some(pkg.changelog.next_release_version.clone())),
+        false => Ok(None),
+    }
+}
+
+/// Computes, writes and publishes or previews the release of a package, returns whether there is a release
+async fn release_package(
+    pkg: &mut Pkg,
+    ctx: &Ctx,
+    record_commits: bool,
+    commits_out: &mut serde_json::Map<String, serde_json::Value>,
+    combined_notes: &mut Vec<String>,
+) -> Result<bool> {
+    // Last release, commits and changelog
+    if next_version(pkg, ctx)?.is_none() {
+        return Ok(false);
+    }
+
+    if record_commits {
+        let key = match pkg.name.is_empty() {
+            true => "root".to_string(),
+            false => pkg.name.clone(),
+        };
+
+        commits_out.insert(key, pkg.commits_record());
+    }
+
+    // List first time contributors of the release
+    if ctx.include_authors && ctx.new_contributors {
+        pkg.load_new_contributors(ctx).context("Finding new contributors")?;
+    }
+
+    // Annotate commits that already shipped in pre releases of this stable release
+    if ctx.annotate_pre_releases && ctx.pre_id.is_empty() {
+        pkg.annotate_pre_releases(&ctx.git).context("Annotating pre releases")?;
+    }
+
+    // Changelog, bump files and the release commit live on a dedicated branch
+    let original_branch = match !ctx.preview && !ctx.bump_files_branch.is_empty() {
+        true => Some(
+            ctx.git.switch_branch(&ctx.bump_files_branch)
+                .context(format!("Checking out {}", ctx.bump_files_branch))?
+        ),
+        false => None,
+    };
+
+    let written = write_release(pkg, ctx).await;
+
+    // Files written by a failed release are not left behind for the release commit of the next package
+    if written.is_err() && !ctx.preview {
+        ctx.git.undo_changes(&pkg.changelog_paths(&ctx.types, &ctx.changelog_file), true)
+            .and_then(|_| ctx.git.undo_changes(&pkg.bumped_paths(ctx), false))
+            .unwrap_or_else(|e| {
+                logWarn!("Restoring files of the failed release - {}", e.to_string());
+            });
+    }
+
+    restore_branch(&ctx.git, &original_branch);
+
+    written?;
+
+    // Written after publishing so it is not part of the release commit
+    if !ctx.write_version_file.is_empty() {
+        pkg.write_version_file(&ctx.write_version_file, ctx.version_file_prefix)
+            .context("Writing version file")?;
+    }
+
+    // Nothing was published by a dry run publish
+    if ctx.preview || ctx.dry_run_publish {
+        return Ok(true);
+    }
+
+    // Mark the released tag as deployed, the release itself already succeeded
+    if !ctx.create_deployment.is_empty() {
+        ctx.api.create_deployment(&pkg.changelog.next_release_version, &ctx.create_deployment)
+            .await
+            .unwrap_or_else(|e| {
+                logWarn!("Creating deployment - {}", e.to_string());
+            });
+    }
+
+    if ctx.combined_release {
+        let heading = match pkg.name.is_empty() {
+            true => ctx.git.repo.clone(),
+            false => pkg.name.clone(),
+        };
+
+        combined_notes.push(format!("# {}\r\n\r\n{}", heading, pkg.changelog.notes));
+    }
+
+    // Clean pre releases
+    if ctx.pre_id.is_empty() && ctx.clean_pre_releases {
+        pkg.clean_pre_releases(&ctx.git, ctx.releases.as_ref(), &ctx.preview)
+            .await
+            .unwrap_or_else(|e| {
+                logInfo!("Cleaning pre releases had some issues - {}", e.to_string());
+            });
+    }
+
+    Ok(true)
+}
+
+/// Writes the release notes and previews or publishes the release of a package
+async fn write_release(pkg: &mut Pkg, ctx: &Ctx) -> Result<()> {
+    // A misconfigured bump file stops the release before anything is written
+    if !ctx.preview {
+        pkg.check_bump_files(ctx).context("Checking bump files")?;
+    }
+
+    // Build metadata of the bump files is read before they are bumped
+    if ctx.header_include_metadata || !ctx.write_version_file.is_empty() {
+        pkg.load_build_metadata(ctx).context("Reading build metadata")?;
+    }
+
+    // Show the build metadata of the bump files in the notes header
+    if ctx.header_include_metadata {
+        pkg.changelog.build_metadata = pkg.build_metadata.clone();
+    }
+
+    // Write release notes
+    pkg.write_notes(&ctx.preview, &ctx.git, &ctx.types, &ctx.changelog_file, &ctx.notes, &ctx.include_authors)
+        .context("Writing release notes")?;
+
+    // Preview release
+    if ctx.preview {
+        logInfo!("Previewing release");
+
+        // Notes are part of the JSON summary
+        if ctx.output == "json" {
+            return Ok(());
+        }
+
+        // Notes of every package are printed as one JSON array after the run
+        if ctx.format == "json" {
+            return Ok(());
+        }
+
+        for line in pkg.changelog.notes.lines() {
+            println!("{}", line);
+        }
+
+        println!();
+
+        return Ok(());
+    }
+
+    // Summary of the irreversible steps ahead
+    if ctx.output != "json" {
+        pkg.print_preflight(ctx).context("Summarizing release")?;
+    }
+
+    // Last chance to stop a local release, the written changelog files are restored
+    if !ctx.yes && io::stdin().is_terminal() && !confirm_publish(pkg)? {
+        ctx.git.undo_changes(&pkg.changelog_paths(&ctx.types, &ctx.changelog_file), true)
+            .context("Restoring changelog files")?;
+
+        bail!("release cancelled");
+    }
+
+    // Bump files
+    pkg.bump_files(ctx).context("Bumping files")?;
+
+    // Update manifest
+    if !ctx.manifest_file.is_empty() {
+        pkg.update_manifest(&ctx.manifest_file).context("Updating manifest")?;
+    }
+
+    // Publish release
+    pkg.publish_release(ctx).await.context("Publishing release")?;
+
+    Ok(())
+}
+
+/// Shows the release notes and asks whether to publish the release
+fn confirm_publish(pkg: &Pkg) -> Result<bool> {
+    eprintln!();
+    for line in pkg.changelog.notes.lines() {
+        eprintln!("{}", line);
+    }
+    eprintln!();
+    eprint!("Publish {}? [y/N] ", pkg.changelog.next_release_version);
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Checks out the branch that was active before switching to the bump files branch
+fn restore_branch(git: &git::Git, branch: &Option<String>) {
+    if let Some(branch) = branch {
+        git.checkout(branch).unwrap_or_else(|e| {
+            logWarn!("Restoring branch {} - {}", branch, e.to_string());
+        });
+    }
+}
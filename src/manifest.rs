@@ -0,0 +1,125 @@
+use anyhow::{Context, Result, bail, Ok};
+use semver::Version;
+use serde_json::{Map, Value};
+use std::{
+    fs,
+    io::Write,
+};
+
+/// Reads a release-please style manifest (`.release-please-manifest.json`) mapping package paths to their
+/// current versions.
+///
+/// ## Arguments
+///
+/// * `file_path` - A string slice that holds the path to the manifest file.
+///
+/// ## Returns
+///
+/// A vector of `(path, version)` tuples in the order they appear in the manifest. The root package is
+/// represented by the `.` path.
+pub fn read_manifest(file_path: &str) -> Result<Vec<(String, Version)>> {
+    let content = fs::read_to_string(file_path)
+        .context(format!("failed to read manifest file {}", file_path))?;
+    let json: Map<String, Value> = serde_json::from_str(&content)
+        .context(format!("failed to parse manifest file {}", file_path))?;
+
+    let mut entries = vec![];
+
+    for (path, version) in json {
+        let version = match version.as_str() {
+            Some(v) => Version::parse(v)
+                .context(format!("invalid version for {} in manifest file {}", path, file_path))?,
+            None => bail!("version for {} in manifest file {} must be a string", path, file_path),
+        };
+
+        entries.push((path.trim_end_matches("/").to_string(), version));
+    }
+
+    Ok(entries)
+}
+
+/// Updates the version of a package path in a release-please style manifest and writes it back to disk.
+///
+/// ## Arguments
+///
+/// * `file_path` - A string slice that holds the path to the manifest file.
+/// * `package_path` - A string slice that holds the package path, an empty path targets the root (`.`) entry.
+/// * `version` - A string slice that holds the new version to be set.
+pub fn update_manifest(file_path: &str, package_path: &str, version: &str) -> Result<()> {
+    let content = fs::read_to_string(file_path)
+        .context(format!("failed to read manifest file {}", file_path))?;
+    let mut json: Map<String, Value> = serde_json::from_str(&content)
+        .context(format!("failed to parse manifest file {}", file_path))?;
+
+    let path = package_path.trim_end_matches("/");
+    let path = match path.is_empty() {
+        true => ".",
+        false => path,
+    };
+
+    // Paths are compared without trailing slashes so the existing entry is updated in place
+    let key = json
+        .keys()
+        .find(|key| key.trim_end_matches("/") == path)
+        .cloned()
+        .unwrap_or(path.to_string());
+
+    json.insert(key, Value::String(version.to_string()));
+
+    let content = serde_json::to_string_pretty(&json)?;
+    let mut file = fs::File::create(file_path)
+        .context(format!("failed to write manifest file {}", file_path))?;
+    file.write_all(format!("{}\n", content).as_bytes())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_is_read_and_updated() {
+        let dir = std::env::temp_dir().join(format!("donder-release-manifest-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let file_path = dir.join(".release-please-manifest.json").to_string_lossy().to_string();
+        fs::write(&file_path, "{\n  \".\": \"1.2.3\",\n  \"packages/api/\": \"0.4.0\"\n}\n").unwrap();
+
+        let entries = read_manifest(&file_path).unwrap();
+
+        update_manifest(&file_path, "packages/api/", "0.5.0").unwrap();
+        update_manifest(&file_path, "", "1.3.0").unwrap();
+        update_manifest(&file_path, "packages/web", "0.1.0").unwrap();
+        let updated = fs::read_to_string(&file_path).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(entries, vec![
+            (".".to_string(), Version::parse("1.2.3").unwrap()),
+            ("packages/api".to_string(), Version::parse("0.4.0").unwrap()),
+        ]);
+        assert_eq!(
+            updated,
+            "{\n  \".\": \"1.3.0\",\n  \"packages/api/\": \"0.5.0\",\n  \"packages/web\": \"0.1.0\"\n}\n",
+        );
+    }
+
+    #[test]
+    fn invalid_manifest_versions_fail() {
+        let dir = std::env::temp_dir().join(format!("donder-release-manifest-invalid-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let file_path = dir.join(".release-please-manifest.json").to_string_lossy().to_string();
+        fs::write(&file_path, "{ \".\": 1 }").unwrap();
+        let not_a_string = read_manifest(&file_path).unwrap_err();
+
+        fs::write(&file_path, "{ \".\": \"latest\" }").unwrap();
+        let not_a_version = read_manifest(&file_path).unwrap_err();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(not_a_string.to_string().contains("must be a string"), "{}", not_a_string);
+        assert!(not_a_version.to_string().contains("invalid version for ."), "{}", not_a_version);
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     path,
     fs,
     io::{Read, Write, Seek, SeekFrom},
@@ -7,13 +8,17 @@ use std::{
 };
 use anyhow::{Context, Result, bail, Ok};
 use serde::Deserialize;
-use chrono::Local;
+use chrono::{Local, NaiveDate, Utc};
 use semver::{Version, Prerelease, BuildMetadata};
+use regex::Regex;
+use serde_json::{json, Value};
 
 use crate::{
     git::{ReleaseInfo, Commits, Git},
     bump_files::*,
-    changelog::Changelog, api::GithubApi, ctx::ReleaseTypes,
+    changelog::{Changelog, ChangelogCommit, NotesOptions}, api::ReleaseProvider, ctx::{Ctx, ReleaseTypes},
+    manifest::update_manifest,
+    actions::write_output,
 };
 
 #[derive(Debug)]
@@ -32,27 +37,79 @@ pub struct Pkg {
     pub commits: Commits,
     // Combination of package name and context tag_prefix
     pub tag_prefix: String,
+    /// Release message of the release commit, the context release_message unless overridden
+    pub release_message: String,
+    /// Glob paths of the files whose commits belong to the package instead of its path
+    pub include_paths: Vec<String>,
+    /// Glob paths of the files whose commits do not belong to the package
+    pub exclude_paths: Vec<String>,
+    /// Names of the packages this package is released with
+    pub depends_on: Vec<String>,
+    /// Releases of the packages this package depends on made in this run
+    pub dependency_updates: Vec<DependencyUpdate>,
+    /// Current version seeded from a release manifest, takes precedence over git tags
+    pub manifest_version: Option<Version>,
+    /// Build metadata the bump files will be written with
+    pub build_metadata: String,
+    /// Url of the published release notes
+    pub release_url: String,
+    /// Revision range the commits were retrieved from, all commits when empty
+    pub commits_range: String,
 }
 
 impl Pkg {
     pub fn new(name: String, path: String, tag_prefix: String, bump_files: BumpFiles) -> Result<Self> {
-        Ok(
-            Self {
-                tag_prefix: match name.is_empty() {
-                    true => tag_prefix,
-                    false => format!("{}@{}", name, tag_prefix),
-                },
-                name,
-                path,
-                bump_files,
-                last_release: ReleaseInfo::new("0.0.0", "", false),
-                changelog: Changelog::new(),
-                commits: Commits::new(),
-            }
-        )
+        let mut pkg = Self {
+            name,
+            path,
+            bump_files,
+            last_release: ReleaseInfo::new("0.0.0", "", false),
+            changelog: Changelog::new(),
+            commits: Commits::new(),
+            tag_prefix: String::new(),
+            release_message: String::new(),
+            include_paths: vec![],
+            exclude_paths: vec![],
+            depends_on: vec![],
+            dependency_updates: vec![],
+            manifest_version: None,
+            build_metadata: String::new(),
+            release_url: String::new(),
+            commits_range: String::new(),
+        };
+        pkg.set_tag_prefix(&tag_prefix);
+
+        Ok(pkg)
+    }
+
+    /// Combines the package name with a tag prefix
+    pub fn set_tag_prefix(&mut self, tag_prefix: &str) {
+        self.tag_prefix = match self.name.is_empty() {
+            true => tag_prefix.to_string(),
+            false => format!("{}@{}", self.name, tag_prefix),
+        };
     }
 
-    pub fn last_release(&mut self, git: &Git, pre_id: &str) -> Result<()> {
+    pub fn last_release(&mut self, ctx: &Ctx) -> Result<()> {
+        let git = &ctx.git;
+        let pre_id = ctx.pre_id.as_str();
+        let pre_id_separator = ctx.pre_id_separator.as_str();
+
+        // Current version comes from the release manifest
+        if let Some(version) = &self.manifest_version {
+            self.last_release = ReleaseInfo::new(&version.to_string(), &self.tag_prefix, false);
+
+            logInfo!("Last release from manifest: {}", self.last_release.tag());
+
+            // Commits are analyzed since the matching tag when it exists, otherwise the whole history is used
+            match git.tag_head(&self.last_release.tag()).ok() {
+                Some(head) => self.last_release.update_head(&head),
+                None => logInfo!("No tag found for manifest version, analyzing all commits"),
+            }
+
+            return Ok(())
+        }
+
         let tags = git.get_tags(&self.tag_prefix)
             .context("failed to get tags")?;
 
@@ -70,10 +127,19 @@ impl Pkg {
         }
 
         if self.last_release.version == ReleaseInfo::new("0.0.0", "", false).version {
+            // Tags may be missing because of a shallow clone or a wrong tag prefix
+            if ctx.error_on_no_previous_release {
+                bail!("no previous release found with tag prefix {}, make sure tags are fetched", self.tag_prefix);
+            }
+
             logInfo!("No previous release found, assuming first release.");
 
             if !pre_id.is_empty() {
-                self.last_release = ReleaseInfo::new(&format!("1.0.0-{}.0", pre_id), &self.tag_prefix, true)
+                self.last_release = ReleaseInfo::new(
+                    &format!("1.0.0-{}{}0", pre_id, pre_id_separator),
+                    &self.tag_prefix,
+                    true,
+                )
             } else {
                 self.last_release = ReleaseInfo::new("1.0.0", &self.tag_prefix, true)
             }
@@ -90,36 +156,197 @@ impl Pkg {
         Ok(())
     }
 
-    pub fn get_commits(&mut self, git: &Git) -> Result<()> {
-        match &self.last_release.initial {
+    /// git log pathspecs of the package, its path unless glob paths are configured
+    pub fn pathspecs(&self) -> Vec<String> {
+        let mut pathspecs = match self.include_paths.is_empty() {
+            true if self.path.is_empty() => vec![],
+            true => vec![self.path.clone()],
+            false => self.include_paths.iter().map(|p| format!(":(glob){}", p)).collect(),
+        };
+
+        pathspecs.extend(self.exclude_paths.iter().map(|p| format!(":(glob,exclude){}", p)));
+
+        pathspecs
+    }
+
+    pub fn get_commits(&mut self, ctx: &Ctx) -> Result<()> {
+        self.commits_range = self.revision_range(ctx)?;
+        self.commits = ctx.git.get_range_commits(&self.commits_range, &self.pathspecs())
+            .context("failed to get commits")?;
+
+        Ok(())
+    }
+
+    /// Revision range of the commits since the last release, empty for the whole history
+    fn revision_range(&self, ctx: &Ctx) -> Result<String> {
+        let git = &ctx.git;
+        let first_release_since = ctx.first_release_since.as_str();
+
+        // A pull request range overrides the tag based range
+        if !ctx.pr_range.is_empty() {
+            logInfo!("Retrieving commits of pull request range {}", ctx.pr_range);
+            return Ok(ctx.pr_range.clone());
+        }
+
+        let range = match &self.last_release.initial {
+            true if !first_release_since.is_empty() => {
+                // A date bounds the range at the last commit made before it, otherwise it is a ref
+                let since_head = match NaiveDate::parse_from_str(first_release_since, "%Y-%m-%d").is_ok() {
+                    true => git.commit_before(first_release_since)
+                        .context("failed to resolve first_release_since date")?,
+                    false => git.tag_head(first_release_since)
+                        .context(format!("failed to resolve first_release_since ref {}", first_release_since))?,
+                };
+
+                logInfo!("Retrieving commits since {}", first_release_since);
+                format!("{}..HEAD", since_head)
+            },
             true => {
                 logInfo!("Retrieving all commits");
-                self.commits = git.get_commits("", &self.path)
-                    .context("failed to get commits")?;
+                String::new()
+            },
+            // Versions seeded from the manifest may have no tag to start from
+            false if self.last_release.head.is_empty() => {
+                logInfo!("Retrieving all commits");
+                String::new()
             },
             false => {
                 logInfo!("Retrieving commits since head {}", self.last_release.head);
+                format!("{}..HEAD", self.last_release.head)
+            }
+        };
 
-                 self.commits = git.get_commits(&self.last_release.head, &self.path)
-                    .context("failed to get commits")?;
+        Ok(range)
+    }
+
+    /// Keeps only the commits where most of the changed files are under the package path
+    pub fn attribute_commits(&mut self, git: &Git) -> Result<()> {
+        let pathspecs = self.pathspecs();
+
+        if pathspecs.is_empty() {
+            return Ok(())
+        }
+
+        // Files of the package are the ones matching its include and exclude paths
+        let files = git.changed_files(&self.commits_range, &[])?;
+        let package_files = git.changed_files(&self.commits_range, &pathspecs)?;
+
+        let mut attributed = Commits::new();
+
+        for commit in std::mem::take(&mut self.commits) {
+            // Merge commits change no files of their own and are kept like commits without a full hash
+            let count = |files: &HashMap<String, Vec<String>>| files.get(&commit.full_hash).map_or(0, |f| f.len());
+            let (total, in_package) = (count(&files), count(&package_files));
+
+            if total == 0 || in_package * 2 > total {
+                attributed.push(commit);
+            } else {
+                logInfo!("Commit {} mostly changes files outside the package paths, skipping", commit.hash);
             }
         }
 
+        self.commits = attributed;
+
         Ok(())
     }
 
-    pub fn load_changelog(&mut self, pre_id: &str, types: &ReleaseTypes) -> Result<bool> {
+    pub fn load_changelog(&mut self, ctx: &Ctx) -> Result<bool> {
+        let released = self.analyze_commits(ctx)?;
+
+        // Commits of excluded authors that counted for the bump are still left out of the notes
+        if !ctx.exclude_authors_from_bump {
+            self.changelog.commits.retain(|c| !ctx.exclude_authors.contains(&c.author));
+        }
+
+        Ok(released)
+    }
+
+    /// Parses the commits into the changelog and computes the next release version, returns whether to release
+    fn analyze_commits(&mut self, ctx: &Ctx) -> Result<bool> {
+        let pre_id = ctx.pre_id.as_str();
+        let types = &ctx.types;
+
         logInfo!("Analyzing {} commits for changelog", self.commits.len());
         
-        // Get a vector of all release types
+        // Get a vector of all release types, hidden types have no bump
         let release_types: Vec<String> = types
             .iter()
+            .filter(|t| !t.bump.is_empty())
             .map(|t| t.commit_type.clone())
             .collect();
 
+        // Commits of excluded authors are dropped here unless they still count for the bump
+        let exclude_authors: &[String] = match ctx.exclude_authors_from_bump {
+            true => &ctx.exclude_authors,
+            false => &[],
+        };
+
         // Parse commits
         for commit in &self.commits {
-            self.changelog.parse_commit(&release_types, commit)
+            self.changelog.parse_commit(
+                &release_types,
+                exclude_authors,
+                &ctx.ignore_commit_pattern,
+                ctx.allow_empty_description,
+                commit,
+            )
+        }
+
+        // Every commit in range must follow the conventional format, merge, release and ignored commits excepted
+        if ctx.strict {
+            let conventional = Regex::new(r"^\w+(\([\w\-\.]+\))?(!)?: .+").unwrap();
+            let release_commit = Regex::new(
+                &format!("^{}$", regex::escape(&self.release_message).replace("%s", ".+"))
+            ).unwrap();
+
+            let offending = self.commits
+                .iter()
+                .filter(|c| !c.subject.is_empty()
+                    && !c.subject.starts_with("Merge ")
+                    && (ctx.ignore_commit_pattern.is_empty()
+                        || !(c.subject.contains(&ctx.ignore_commit_pattern)
+                            || c.body.contains(&ctx.ignore_commit_pattern)))
+                    && !release_commit.is_match(&c.subject)
+                    && !conventional.is_match(&c.subject))
+                .map(|c| format!("{} {}", c.hash, c.subject))
+                .collect::<Vec<String>>();
+
+            if !offending.is_empty() {
+                bail!("non conventional commits found in strict mode:\n{}", offending.join("\n"));
+            }
+        }
+
+        // Commits of hidden types (e.g: docs) still ship as a patch release
+        if self.changelog.commits.is_empty() && ctx.patch_on_hidden_only {
+            let hidden_types: Vec<String> = types
+                .iter()
+                .filter(|t| t.bump.is_empty())
+                .map(|t| t.commit_type.clone())
+                .collect();
+
+            for commit in &self.commits {
+                self.changelog.parse_commit(
+                    &hidden_types,
+                    exclude_authors,
+                    &ctx.ignore_commit_pattern,
+                    ctx.allow_empty_description,
+                    commit,
+                )
+            }
+
+            if !self.changelog.commits.is_empty() {
+                logInfo!("Only hidden commits found, releasing a patch");
+            }
+        }
+
+        // Releases of the packages this package depends on release at least a patch
+        for update in &self.dependency_updates {
+            self.changelog.commits.push(ChangelogCommit {
+                section_type: "fix".to_string(),
+                scope: "deps".to_string(),
+                desc: format!("update {} to {}", update.package, update.version),
+                ..Default::default()
+            });
         }
 
         if self.changelog.commits.is_empty() {
@@ -129,10 +356,58 @@ impl Pkg {
         
         logInfo!("Found {} relevant commits", self.changelog.commits.len());
 
+        // An explicit version replaces the computed one
+        if !ctx.set_version.is_empty() {
+            let version = Version::parse(&ctx.set_version).context("set version must be a valid semver version")?;
+
+            if !self.last_release.initial && version <= self.last_release.version {
+                bail!("set version {} must be greater than the last release {}", version, self.last_release.version);
+            }
+
+            self.changelog.next_release_version = format!("{}{}", &self.tag_prefix, version);
+
+            if ctx.explain {
+                logInfo!("Explain: version set explicitly, bump calculation skipped");
+            }
+
+            self.check_max_version(ctx)?;
+
+            logInfo!("Next release version: {}", self.changelog.next_release_version);
+
+            return Ok(true)
+        }
+
+        // Batch small changes until enough relevant commits are found, breaking changes may skip the wait
+        if self.changelog.commits.len() < ctx.min_commits.count() {
+            let has_breaking = self.changelog.commits.iter().any(|c| !c.breaking.is_empty());
+
+            if !(ctx.min_commits.always_release_breaking() && has_breaking) {
+                logInfo!(
+                    "Only {} relevant commits, below min_commits {}, skipping release",
+                    self.changelog.commits.len(),
+                    ctx.min_commits.count(),
+                );
+                return Ok(false)
+            }
+        }
+
+        // Calendar versions follow the release date, commit types only decide whether to release
+        if ctx.versioning == "calver" {
+            self.load_calver_version(ctx)?;
+
+            return Ok(true)
+        }
+
         // We already have the next release tag
         if self.last_release.initial {
             self.changelog.next_release_version = self.last_release.tag();
+
+            if ctx.explain {
+                logInfo!("Explain: no previous release found, using initial version");
+            }
     
+            self.check_max_version(ctx)?;
+
             logInfo!("Next release version: {}", self.changelog.next_release_version);
 
             return Ok(true)
@@ -152,32 +427,121 @@ impl Pkg {
                 build: BuildMetadata::EMPTY,
             });
     
+            self.check_max_version(ctx)?;
+
             logInfo!("Next release version: {}", self.changelog.next_release_version);
 
             return Ok(true)
         }
 
-        next_release = next_release.replace(&self.tag_prefix, "");
+        next_release = next_release.strip_prefix(&self.tag_prefix).unwrap_or(&next_release).to_string();
+
+        if ctx.explain {
+            logInfo!("Explain: base version {}", next_release);
+        }
+
+        // Commits reverted in the same range do not contribute to the bump
+        let reverted_hashes = match ctx.reverts_affect_bump {
+            true => {
+                let re = Regex::new(r"This reverts commit ([0-9a-f]+)").unwrap();
+
+                self.commits
+                    .iter()
+                    .filter_map(|c| re.captures(&c.body).map(|caps| caps[1].to_string()))
+                    .collect::<Vec<String>>()
+            },
+            false => vec![],
+        };
+
+        let is_reverted = |commit: &ChangelogCommit| {
+            !commit.hash.is_empty() && reverted_hashes.iter().any(|h| h.starts_with(&commit.hash))
+        };
+
+        // Commit that triggered the highest bump
+        let mut trigger = self.changelog.commits.iter().find(|c| !is_reverted(c));
 
         // Get next release type
         for commit in &self.changelog.commits {
-            if !commit.breaking.is_empty() {
+            if is_reverted(commit) {
+                if ctx.explain {
+                    logInfo!("Explain: {} {} was reverted, ignored for bump", commit.hash, commit.subject());
+                }
+
+                continue;
+            }
+
+            // Types with a major bump release a major version like breaking changes do
+            let major_type = types
+                .iter()
+                .any(|t| t.commit_type == commit.section_type && t.bump == "major");
+
+            if !commit.breaking.is_empty() || major_type {
                 next_release_type = "major".to_string();
+                trigger = Some(commit);
                 break;
             }
 
             for release_type in types {
                 if commit.section_type == release_type.commit_type && release_type.bump == "minor" {
+                    if next_release_type != "minor" {
+                        trigger = Some(commit);
+                    }
+
                     next_release_type = "minor".to_string();
                     break;
                 }
             }
         }
 
+        // Only build commits keep the version and increment its build metadata instead
+        let build_only = self.changelog.commits.iter().all(|commit| {
+            commit.breaking.is_empty() && types
+                .iter()
+                .any(|t| t.commit_type == commit.section_type && t.bump == "build")
+        });
+
+        if build_only {
+            next_release_type = "build".to_string();
+        }
+
+        if ctx.explain {
+            if let Some(commit) = trigger {
+                logInfo!(
+                    "Explain: {} bump triggered by {} {}",
+                    next_release_type,
+                    commit.hash,
+                    commit.subject(),
+                );
+            }
+        }
+
         // Get next release version
         let mut next_release_version = semver::Version::parse(&next_release)
             .context("failed to parse next release version")?;
 
+        if next_release_type == "build" {
+            let build = next_release_version.build.as_str().parse::<u64>().unwrap_or(0) + 1;
+
+            next_release_version.build = BuildMetadata::new(&build.to_string())
+                .context("failed to update build metadata")?;
+
+            self.changelog.next_release_version = format!("{}{}", &self.tag_prefix, next_release_version);
+
+            if ctx.explain {
+                logInfo!("Explain: only build commits found, build metadata incremented to {}", build);
+            }
+
+            self.check_max_version(ctx)?;
+
+            logInfo!("Next release version: {}", self.changelog.next_release_version);
+
+            return Ok(true)
+        }
+
+        if ctx.explain && !next_release_version.pre.is_empty() {
+            logInfo!("Explain: base version is a pre release, major, minor and patch are kept");
+        }
+
         if next_release_version.pre.is_empty() {
             next_release_version = match next_release_type.as_str() {
                 "major" => Version {
@@ -206,41 +570,156 @@ impl Pkg {
         }
 
         if !pre_id.is_empty() {
-            let mut pre = next_release_version.pre;
+            next_release_version = pre_release_version(ctx, next_release_version)?;
+        }
 
-            if pre.is_empty() {
-                pre = Prerelease::new(format!("{}.0", pre_id.clone()).as_str())
-                    .context("failed to update pre release")?
-            } else {
-                let parts = pre.split(".").collect::<Vec<&str>>();
-
-                if parts[0] == pre_id {
-                    pre = Prerelease::new(
-                        format!("{}.{}", pre_id.clone(), parts[1].parse::<u32>().unwrap() + 1).as_str(),
-                    ).context("failed to update pre release")?
-                } else {
-                    pre = Prerelease::new(format!("{}.0", pre_id.clone()).as_str())
-                        .context("failed to update pre release")?
-                }
-            }
+        self.changelog.next_release_version = format!("{}{}", &self.tag_prefix, next_release_version);
 
-            next_release_version = Version {
-                major: next_release_version.major,
-                minor: next_release_version.minor,
-                patch: next_release_version.patch,
-                pre,
-                build: BuildMetadata::EMPTY,
+        if ctx.explain {
+            logInfo!("Explain: {} bump applied to {} results in {}", next_release_type, next_release, next_release_version);
+        }
+
+        self.check_max_version(ctx)?;
+
+        logInfo!("Next release version: {}", self.changelog.next_release_version);
+
+        Ok(true)
+    }
+
+    /// Computes the next calendar version, the micro version increments within the same period
+    fn load_calver_version(&mut self, ctx: &Ctx) -> Result<()> {
+        let period = match ctx.notes.changelog_timezone.as_str() {
+            "local" => Local::now().format(&ctx.calver_format).to_string(),
+            _ => Utc::now().format(&ctx.calver_format).to_string(),
+        };
+        let mut next_release_version = Version::parse(&format!("{}.0", period))
+            .context("failed to parse calendar version")?;
+
+        let last_version = &self.last_release.version;
+        let same_period = !self.last_release.initial
+            && last_version.major == next_release_version.major
+            && last_version.minor == next_release_version.minor;
+
+        // A pre release of the period keeps its micro version
+        if same_period {
+            next_release_version.patch = match last_version.pre.is_empty() {
+                true => last_version.patch + 1,
+                false => last_version.patch,
             };
+            next_release_version.pre = last_version.pre.clone();
+        }
+
+        if ctx.explain {
+            match same_period {
+                true => logInfo!("Explain: last release {} is in period {}, micro version continues", last_version, period),
+                false => logInfo!("Explain: first release of period {}, micro version starts at 0", period),
+            }
+        }
+
+        next_release_version = match ctx.pre_id.is_empty() {
+            true => Version { pre: Prerelease::EMPTY, ..next_release_version },
+            false => pre_release_version(ctx, next_release_version)?,
+        };
+
+        // The clock or the format went backwards
+        if !self.last_release.initial && next_release_version <= *last_version {
+            bail!("calendar version {} is not greater than the last release {}", next_release_version, last_version);
         }
 
         self.changelog.next_release_version = format!("{}{}", &self.tag_prefix, next_release_version);
 
+        self.check_max_version(ctx)?;
+
         logInfo!("Next release version: {}", self.changelog.next_release_version);
 
-        Ok(true)
+        Ok(())
+    }
+
+    /// Guards against versions above the configured max_version (e.g: an unexpected major bump)
+    fn check_max_version(&self, ctx: &Ctx) -> Result<()> {
+        if ctx.max_version.is_empty() || ctx.allow_exceed_ceiling {
+            return Ok(())
+        }
+
+        let max_version = Version::parse(&ctx.max_version)
+            .context("failed to parse max_version")?;
+        let next_version = Version::parse(&self.next_version())
+            .context("failed to parse next release version")?;
+
+        if next_version > max_version {
+            bail!(
+                "next release version {} exceeds max_version {}, use --allow-exceed-ceiling to release it",
+                next_version,
+                max_version,
+            );
+        }
+
+        Ok(())
     }
 
-    pub fn write_notes(&mut self, preview: &bool, git: &Git, types: &ReleaseTypes, changelog_file: &str) -> Result<()> {
+    /// Finds the authors of this release without commits before it
+    pub fn load_new_contributors(&mut self, ctx: &Ctx) -> Result<()> {
+        // Every author is new in a first release
+        let previous_authors = match self.last_release.initial || self.last_release.head.is_empty() {
+            true => vec![],
+            false => ctx.git.authors(&self.last_release.head)
+                .context("failed to get previous authors")?,
+        };
+
+        // Oldest commits first so the first contribution is found
+        for commit in self.commits.iter().rev() {
+            if commit.author.is_empty()
+                || ctx.exclude_authors.contains(&commit.author)
+                || previous_authors.contains(&commit.author)
+                || self.changelog.new_contributors.iter().any(|(author, _)| author == &commit.author)
+            {
+                continue;
+            }
+
+            self.changelog.new_contributors.push((commit.author.clone(), commit.hash.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// Annotates commits that already shipped in pre releases of this stable release with the
+    /// first pre release they appeared in.
+    pub fn annotate_pre_releases(&mut self, git: &Git) -> Result<()> {
+        let mut pre_releases = git.get_tags(&self.tag_prefix)
+            .context("failed to get tags")?;
+
+        // Pre releases published after the last stable release, oldest first
+        pre_releases.retain(|tag| !tag.version.pre.is_empty()
+            && (self.last_release.initial || tag.version > self.last_release.version));
+        pre_releases.reverse();
+
+        for pre_release in pre_releases {
+            let range = match self.last_release.initial {
+                true => pre_release.tag(),
+                false => format!("{}..{}", self.last_release.head, pre_release.tag()),
+            };
+            let hashes = git.commit_hashes(&range, &self.pathspecs())
+                .context("failed to get pre release commits")?;
+
+            for commit in self.changelog.commits.iter_mut() {
+                if commit.pre_release.is_empty() && hashes.contains(&commit.hash) {
+                    commit.pre_release = pre_release.version.to_string();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn write_notes(
+        &mut self,
+        preview: &bool,
+        git: &Git,
+        types: &ReleaseTypes,
+        changelog_file: &str,
+        notes_options: &NotesOptions,
+        include_authors: &bool,
+    ) -> Result<()> {
         logInfo!("Writing release notes");
 
         let origin_url = git.origin_url().context("failed to get git orin url")?;
@@ -249,32 +728,152 @@ impl Pkg {
             &self.last_release.tag(),
             types,
             origin_url.as_str(),
+            notes_options,
+            include_authors,
         ).context("failed to write release notes")?;
 
         // Write to file if specified and not in preview mode
         if !preview && !changelog_file.is_empty() {
-            let changelog_file_with_root = match !self.path.is_empty() {
-                true => format!("{}/{}", self.path, changelog_file),
-                false => changelog_file.to_string(),
-            };
-            let path = path::PathBuf::from(&changelog_file_with_root);
-            let changelog_title = "# CHANGELOG\r\n\r\n_This file is auto-generated by donder-release and should not be edited manually._\r\n\r\n";
+            // Sections of types with an exclusive changelog file are left out of the main changelog
+            let exclusive_types = types
+                .iter()
+                .filter(|t| !t.changelog_file.is_empty() && t.changelog_exclusive)
+                .map(|t| t.commit_type.clone())
+                .collect::<Vec<String>>();
+
+            match exclusive_types.is_empty() {
+                true => self.write_changelog_file(changelog_file, &self.changelog.notes)?,
+                false => {
+                    let notes = self.changelog.render_notes(
+                        |commit| !exclusive_types.contains(&commit.section_type),
+                        &self.last_release.tag(),
+                        types,
+                        origin_url.as_str(),
+                        notes_options,
+                        include_authors,
+                    )?;
+
+                    self.write_changelog_file(changelog_file, &notes)?;
+                },
+            }
+        }
+
+        // Write sections to their dedicated changelog files
+        if !preview {
+            let mut type_files = types
+                .iter()
+                .filter(|t| !t.changelog_file.is_empty())
+                .map(|t| t.changelog_file.clone())
+                .collect::<Vec<String>>();
+            type_files.sort();
+            type_files.dedup();
+
+            for type_file in type_files {
+                let file_types = types
+                    .iter()
+                    .filter(|t| t.changelog_file == type_file)
+                    .map(|t| t.commit_type.clone())
+                    .collect::<Vec<String>>();
+
+                // Only write files that have commits in this release
+                if !self.changelog.commits.iter().any(|c| file_types.contains(&c.section_type)) {
+                    continue;
+                }
+
+                let notes = self.changelog.render_notes(
+                    |commit| file_types.contains(&commit.section_type),
+                    &self.last_release.tag(),
+                    types,
+                    origin_url.as_str(),
+                    notes_options,
+                    include_authors,
+                )?;
+
+                self.write_changelog_file(&type_file, &notes)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Release notes as structured JSON
+    pub fn notes_json(&self, ctx: &Ctx) -> Result<Value> {
+        let origin_url = ctx.git.origin_url().context("failed to get git origin url")?;
+
+        Ok(self.changelog.to_json(
+            &self.last_release.tag(),
+            &ctx.types,
+            origin_url.as_str(),
+            &ctx.notes,
+            &ctx.include_authors,
+        ))
+    }
+
+    /// Paths of the bump files and the manifest file written by a release
+    pub fn bumped_paths(&self, ctx: &Ctx) -> Vec<String> {
+        let mut paths = self.bump_files
+            .iter()
+            .filter_map(|file| target_file_path(&file.target, &file.path).ok())
+            .collect::<Vec<String>>();
+
+        if !ctx.manifest_file.is_empty() {
+            paths.push(ctx.manifest_file.clone());
+        }
 
-            // Check if changelog file exists on disk
-            if path.exists() {
-                // Write notes after changelog title and before first release
-                let mut file = fs::OpenOptions::new()
-                    .read(true)
-                    .write(true)
-                    .open(&path)
-                    .context("failed to open changelog file")?;
+        paths
+    }
+
+    /// Paths of the changelog files written by write_notes
+    pub fn changelog_paths(&self, types: &ReleaseTypes, changelog_file: &str) -> Vec<String> {
+        let mut files = types
+            .iter()
+            .filter(|t| !t.changelog_file.is_empty())
+            .filter(|t| self.changelog.commits.iter().any(|c| c.section_type == t.commit_type))
+            .map(|t| t.changelog_file.clone())
+            .collect::<Vec<String>>();
+
+        if !changelog_file.is_empty() {
+            files.push(changelog_file.to_string());
+        }
+
+        files.sort();
+        files.dedup();
+
+        files.iter().map(|file| self.package_file_path(file)).collect()
+    }
+
+    /// Path of a file in the package folder (e.g: the changelog file)
+    fn package_file_path(&self, file: &str) -> String {
+        match !self.path.is_empty() {
+            true => format!("{}/{}", self.path, file),
+            false => file.to_string(),
+        }
+    }
+
+    fn write_changelog_file(&self, changelog_file: &str, notes: &str) -> Result<()> {
+        let changelog_file_with_root = self.package_file_path(changelog_file);
+        let path = path::PathBuf::from(&changelog_file_with_root);
+        let changelog_title = "# CHANGELOG\r\n\r\n_This file is auto-generated by donder-release and should not be edited manually._\r\n\r\n";
+
+        // Check if changelog file exists on disk
+        if path.exists() {
+            // Write notes after changelog title and before first release
+            let mut file = fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&path)
+                .context("failed to open changelog file")?;
+
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)
+                .context("failed to read changelog file")?;
 
-                let mut contents = String::new();
-                file.read_to_string(&mut contents)
-                    .context("failed to read changelog file")?;
+            let lines = contents.lines().collect::<Vec<&str>>();
+            let expected_header = changelog_title.lines().take(3).collect::<Vec<&str>>();
+            let mut new_contents;
 
-                let lines = contents.lines().collect::<Vec<&str>>();
-                let mut new_contents = format!("{}{}", changelog_title, self.changelog.notes);
+            if lines.len() >= 3 && lines[..3] == expected_header[..] {
+                new_contents = format!("{}{}", changelog_title, notes);
 
                 // Add remaining lines to new contents
                 for (i, line) in lines.iter().enumerate() {
@@ -284,63 +883,219 @@ impl Pkg {
                         new_contents = format!("{}\r\n{}", new_contents, line);
                     }
                 }
+            } else {
+                logWarn!("Changelog header was manually edited, keeping it as is");
 
-                // New line at end of file
-                new_contents = format!("{}\r\n", new_contents);
+                // Everything before the first release is treated as the header
+                let first_release = lines
+                    .iter()
+                    .position(|line| line.starts_with("## "))
+                    .unwrap_or(lines.len());
+                let header = lines[..first_release].join("\r\n");
 
-                file.set_len(0)
-                    .context("failed to truncate changelog file")?;
+                new_contents = format!("{}\r\n\r\n{}", header.trim_end(), notes);
 
-                file.seek(SeekFrom::Start(0))
-                    .context("failed to seek to start of changelog file")?;
+                // Write old releases back to new contents
+                for line in &lines[first_release..] {
+                    new_contents = format!("{}\r\n{}", new_contents, line);
+                }
+            }
 
-                file.write_all(new_contents.as_bytes())
-                    .context("failed to write to changelog file")?;
-            } else {
-                // Create new changelog file
-                fs::File::create(&path)
-                    .context("failed to create changelog file")?;
-
-                let changelog_content = format!(
-                   "{}{}",
-                    changelog_title,
-                    self.changelog.notes,
-                );
+            // New line at end of file
+            new_contents = format!("{}\r\n", new_contents);
+
+            file.set_len(0)
+                .context("failed to truncate changelog file")?;
+
+            file.seek(SeekFrom::Start(0))
+                .context("failed to seek to start of changelog file")?;
+
+            file.write_all(new_contents.as_bytes())
+                .context("failed to write to changelog file")?;
+        } else {
+            // Create new changelog file
+            fs::File::create(&path)
+                .context("failed to create changelog file")?;
+
+            let changelog_content = format!(
+               "{}{}",
+                changelog_title,
+                notes,
+            );
+
+            fs::write(path, changelog_content)
+                .context("failed to write to changelog file")?;
+        }
+
+        logInfo!("Wrote release notes to {}", changelog_file_with_root);
 
-                fs::write(path, changelog_content)
-                    .context("failed to write to changelog file")?;
+        Ok(())
+    }
+
+    /// Precomputes the build metadata the bump files will be written with, before they are bumped
+    pub fn load_build_metadata(&mut self, ctx: &Ctx) -> Result<()> {
+        let version = self.next_version();
+
+        for file in self.bump_files.iter().filter(|f| f.build_metadata) {
+            if !path::Path::new(&target_file_path(&file.target, &file.path)?).exists() {
+                continue;
+            }
+
+            let bumped_version = bumped_version(file, &version, &ctx.reset_build_metadata_on_version_change)?;
+
+            if let Some((_, build)) = bumped_version.split_once('+') {
+                self.build_metadata = build.to_string();
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks every bump file can be written and holds a version before anything is changed
+    pub fn check_bump_files(&self, ctx: &Ctx) -> Result<()> {
+        for file in &self.bump_files {
+            let file_path = target_file_path(&file.target, &file.path)?;
+
+            if !path::Path::new(&file_path).exists() {
+                match ctx.on_missing_bump_file.as_str() {
+                    "skip" | "warn" => continue,
+                    _ => bail!("bump file {} not found", file_path),
+                }
             }
 
-            logInfo!("Wrote release notes to {}", changelog_file_with_root);
+            fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&file_path)
+                .context(format!("bump file {} is not writable", file_path))?;
+
+            // Fails when the version or the pattern of the file is not found
+            check_version(file)?;
         }
 
         Ok(())
     }
 
-    pub fn bump_files(&self) -> Result<()> {
+    /// Prints what the release will change before anything is published
+    pub fn print_preflight(&self, ctx: &Ctx) -> Result<()> {
+        logInfo!("Pre-flight summary");
+
+        println!("  Version: {} -> {}", self.last_release.tag(), self.changelog.next_release_version);
+        println!("  Tag: {}", self.changelog.next_release_version);
+
+        for file in &self.bump_files {
+            let file_path = target_file_path(&file.target, &file.path)?;
+
+            if !path::Path::new(&file_path).exists() {
+                println!("  Bump {}: not found ({})", file_path, ctx.on_missing_bump_file);
+                continue;
+            }
+
+            let old_version = current_version(file)?.unwrap_or("unknown".to_string());
+            let new_version = bumped_version(file, &self.next_version(), &ctx.reset_build_metadata_on_version_change)?;
+
+            println!("  Bump {}: {} -> {}", file_path, old_version, new_version);
+        }
+
+        match ctx.combined_release {
+            true => println!("  Release target: combined {} release", ctx.notes_target),
+            false => println!("  Release target: {}", ctx.notes_target),
+        }
+
+        println!();
+
+        Ok(())
+    }
+
+    pub fn bump_files(&self, ctx: &Ctx) -> Result<()> {
         logInfo!("Bumping versioning files");
 
-        let version = &self.changelog.next_release_version.replace(&self.tag_prefix, "");
+        let version = &self.next_version();
 
         for file in &self.bump_files {
+            // Handle versioning files that are not present on disk
+            let file_path = target_file_path(&file.target, &file.path)?;
+
+            if !path::Path::new(&file_path).exists() {
+                match ctx.on_missing_bump_file.as_str() {
+                    "skip" => continue,
+                    "warn" => {
+                        logWarn!("Bump file {} not found, skipping", file_path);
+                        continue;
+                    },
+                    _ => bail!("bump file {} not found", file_path),
+                }
+            }
+
             match file.target.as_str() {
                 "cargo" => {
-                    bump_cargo(version, &file.path, &file.build_metadata)?;
+                    bump_cargo(version, &file.path, &file.build_metadata, &ctx.reset_build_metadata_on_version_change)?;
+
+                    // Depend on the versions released in this run
+                    if ctx.update_dependency_versions {
+                        for (name, version) in self.dependency_versions() {
+                            bump_cargo_dependency(&file.path, &name, &version)?;
+                        }
+                    }
+
+                    // Projects without a lockfile are not blocked
+                    if file.update_lock {
+                        update_cargo_lock(&file.path).unwrap_or_else(|e| {
+                            logWarn!("Updating Cargo.lock of {} - {}", file_path, e.to_string());
+                        });
+                    }
                 },
                 "npm" => {
-                    bump_npm(version, &file.path, &file.build_metadata)?;
+                    bump_npm(version, &file.path, &file.build_metadata, &ctx.reset_build_metadata_on_version_change)?;
+
+                    if ctx.update_dependency_versions {
+                        for (name, version) in self.dependency_versions() {
+                            bump_npm_dependency(&file.path, &name, &version)?;
+                        }
+                    }
+                },
+                "composer" => {
+                    bump_composer(version, &file.path, &file.build_metadata, &ctx.reset_build_metadata_on_version_change)?;
                 },
                 "pub" => {
-                    bump_pub(version, &file.path, &file.build_metadata)?;
+                    bump_pub(version, &file.path, &file.build_metadata, &ctx.reset_build_metadata_on_version_change)?;
+                },
+                "python" => {
+                    bump_python(version, &file.path, &file.build_metadata, &ctx.reset_build_metadata_on_version_change)?;
+                },
+                "helm" => {
+                    bump_helm(
+                        version,
+                        &file.path,
+                        &file.app_version,
+                        &file.build_metadata,
+                        &ctx.reset_build_metadata_on_version_change,
+                    )?;
                 },
                 "android" => {
-                    bump_android(version, &file.path)?;
+                    bump_android(version, &file.path, &file.build_metadata)?;
                 },
                 "ios" => {
                     bump_ios(version, &file.path)?;
                 },
+                "custom" | "regex" => {
+                    bump_custom(
+                        version,
+                        &file.path,
+                        &file.pattern,
+                        &file.template,
+                        &file.build_metadata,
+                        &ctx.reset_build_metadata_on_version_change,
+                    )?;
+                },
                 _ => bail!("invalid file bump target"),
             }
+
+            // Stamp the release date
+            if !file.date_key.is_empty() {
+                bump_date(&file.target, &file.path, &file.date_key, &file.date_format)?;
+            }
         }
 
         // Wait a little bit to make sure the files are updated
@@ -349,32 +1104,254 @@ impl Pkg {
         Ok(())
     }
 
-    pub async fn publish_release(&self, git: &Git, api: &GithubApi, release_message: &str) -> Result<()> {
+    /// Release of this package for the packages that depend on it
+    pub fn dependency_update(&self) -> DependencyUpdate {
+        let manifest_names = self.bump_files
+            .iter()
+            .filter_map(|file| manifest_name(&file.target, &file.path).ok().flatten())
+            .collect::<Vec<String>>();
+
+        DependencyUpdate {
+            package: self.name.clone(),
+            version: self.next_version(),
+            manifest_names,
+        }
+    }
+
+    /// Manifest names of the dependencies released in this run and their versions
+    fn dependency_versions(&self) -> Vec<(String, String)> {
+        self.dependency_updates
+            .iter()
+            .flat_map(|update| update.manifest_names.iter().map(|name| (name.clone(), update.version.clone())))
+            .collect()
+    }
+
+    /// Commits that comprise the release with their full hashes, for traceability
+    pub fn commits_record(&self) -> Value {
+        let commits = self.commits
+            .iter()
+            .filter(|c| !c.full_hash.is_empty())
+            .map(|c| json!({ "hash": c.full_hash, "subject": c.subject }))
+            .collect::<Vec<Value>>();
+
+        json!({
+            "version": self.next_version(),
+            "tag": self.changelog.next_release_version,
+            "commits": commits,
+        })
+    }
+
+    /// Next release version without the tag prefix
+    pub fn next_version(&self) -> String {
+        let tag = &self.changelog.next_release_version;
+
+        tag.strip_prefix(&self.tag_prefix).unwrap_or(tag).to_string()
+    }
+
+    /// Writes the released version to a plain file of the package folder, with its build metadata when applied
+    pub fn write_version_file(&self, version_file: &str, with_prefix: bool) -> Result<()> {
+        let version_file = self.package_file_path(version_file);
+
+        let mut version = match with_prefix {
+            true => self.changelog.next_release_version.clone(),
+            false => self.next_version(),
+        };
+
+        if !self.build_metadata.is_empty() {
+            version = format!("{}+{}", version, self.build_metadata);
+        }
+
+        fs::write(&version_file, format!("{}\n", version))
+            .context(format!("failed to write version file {}", version_file))?;
+
+        logInfo!("Wrote version {} to {}", version, version_file);
+
+        Ok(())
+    }
+
+    pub fn write_outputs(&self, has_release: bool, released: bool) -> Result<()> {
+        // Monorepo packages have their outputs prefixed with the package name (e.g: @scope/pkg -> _scope_pkg_)
+        let prefix = match self.name.is_empty() {
+            true => "".to_string(),
+            false => format!("{}_", self.name.replace(|c: char| !c.is_ascii_alphanumeric() && c != '-', "_")),
+        };
+        let (version, tag) = match has_release {
+            true => (
+                self.next_version(),
+                self.changelog.next_release_version.clone(),
+            ),
+            false => ("".to_string(), "".to_string()),
+        };
+
+        write_output(&format!("{}next_version", prefix), &version)?;
+        write_output(&format!("{}tag", prefix), &tag)?;
+        write_output(&format!("{}released", prefix), &released.to_string())?;
+
+        Ok(())
+    }
+
+    /// Machine readable summary of the release for --output json
+    pub fn summary(&self, has_release: bool, released: bool) -> Value {
+        json!({
+            "name": self.name,
+            "previous_version": self.last_release.tag(),
+            "next_version": match has_release {
+                true => self.changelog.next_release_version.clone(),
+                false => String::new(),
+            },
+            "released": released,
+            "release_url": self.release_url,
+            "changelog": match has_release {
+                true => self.changelog.notes.clone(),
+                false => String::new(),
+            },
+        })
+    }
+
+    pub fn update_manifest(&self, manifest_file: &str) -> Result<()> {
+        logInfo!("Updating manifest {}", manifest_file);
+
+        let version = &self.next_version();
+
+        update_manifest(manifest_file, &self.path, version)
+            .context("failed to update manifest")
+    }
+
+    pub async fn publish_release(&mut self, ctx: &Ctx) -> Result<()> {
         logInfo!("Publishing release");
 
-        // Release commit
-        git
-            .commit(release_message.replace("%s", &self.changelog.next_release_version).as_str())?;
+        let git = &ctx.git;
+
+        let tag_exists = git.remote_tag_exists(&self.changelog.next_release_version)?;
+
+        // Release a prior run may have published before failing, it is updated instead of created again. Looked up
+        // before the tag is recreated since releases of deleted tags become drafts.
+        let existing_release = match ctx.update_existing && tag_exists {
+            true => ctx.releases.find_release(&self.changelog.next_release_version).await?,
+            false => None,
+        };
+
+        // A partially completed prior release may have already pushed the tag
+        if tag_exists {
+            // The pushed tag is kept and only its release is updated, files written by this run are restored
+            if existing_release.is_some() && !ctx.force {
+                logWarn!("Tag {} already exists remotely, updating its release", self.changelog.next_release_version);
 
-        // Push to remote
-        git.push()?;
+                git.undo_changes(&self.changelog_paths(&ctx.types, &ctx.changelog_file), true)?;
+                git.undo_changes(&self.bumped_paths(ctx), false)?;
+
+                if ctx.dry_run_publish {
+                    logInfo!("Dry run publish, release {} was not updated", self.changelog.next_release_version);
+                    return Ok(());
+                }
+
+                ctx.releases.publish_release(
+                    &self.changelog.next_release_version,
+                    &self.tag_prefix,
+                    &self.changelog.notes,
+                    &git.tag_head(&self.changelog.next_release_version).unwrap_or_default(),
+                    existing_release.as_deref())
+                    .await?;
+
+                self.release_url = ctx.releases.release_url(&git.origin_url()?, &self.changelog.next_release_version);
+
+                return Ok(());
+            }
+
+            if !ctx.force {
+                bail!("tag {} already exists remotely, use --force to recreate it", self.changelog.next_release_version);
+            }
+
+            logWarn!("Tag {} already exists remotely, recreating it", self.changelog.next_release_version);
+
+            if !ctx.dry_run_publish {
+                git.delete_tag(&self.changelog.next_release_version)?;
+            }
+            // Local tag may not exist
+            git.undo_tag(&self.changelog.next_release_version).ok();
+        }
+
+        // Release commit
+        git.commit(
+            self.release_message.replace("%s", &self.changelog.next_release_version).as_str(),
+            &ctx.release_commit_trailers,
+        )?;
+
+        // Push to remote, the release commit lives on bump_files_branch when defined
+        let branch = match ctx.bump_files_branch.is_empty() {
+            true => &ctx.branch,
+            false => &ctx.bump_files_branch,
+        };
+        if !ctx.dry_run_publish {
+            git.push(branch)?;
+        }
 
         // Release tag
-        git.tag(&self.changelog.next_release_version)?;
+        git.tag(
+            &self.changelog.next_release_version,
+            &ctx.tag_message(&self.changelog.next_release_version, &self.changelog.notes),
+        )?;
+
+        // Dry run publish stops before pushing the tag, the local release commit and tag are undone
+        if ctx.dry_run_publish {
+            logInfo!("Dry run publish, release {} was not created", self.changelog.next_release_version);
+
+            git.undo_tag(&self.changelog.next_release_version)?;
+            git.uncommit()?;
+            git.undo_changes(&self.changelog_paths(&ctx.types, &ctx.changelog_file), true)?;
+            git.undo_changes(&self.bumped_paths(ctx), false)?;
+
+            return Ok(());
+        }
+
         git.push_tag(&self.changelog.next_release_version)?;
 
-        // Create release on GitHub
-        api.publish_release(
+        // Release on GitHub is created once for all packages
+        if ctx.combined_release {
+            return Ok(())
+        }
+
+        // Create release on GitHub, attached to the tagged commit when it can be resolved
+        ctx.releases.publish_release(
             &self.changelog.next_release_version,
             &self.tag_prefix,
-            &self.changelog.notes)
+            &self.changelog.notes,
+            &git.tag_head(&self.changelog.next_release_version).unwrap_or_default(),
+            existing_release.as_deref())
             .await?;
+
+        self.release_url = ctx.releases.release_url(&git.origin_url()?, &self.changelog.next_release_version);
+
+        // Notes are also published to a gist or the wiki
+        match ctx.notes_target.as_str() {
+            "gist" => {
+                let url = ctx.api.create_gist(&self.changelog.next_release_version, &self.changelog.notes).await?;
+                logInfo!("Release notes published to gist {}", url);
+            },
+            "wiki" => {
+                let url = git.publish_wiki_page(&self.changelog.next_release_version, &self.changelog.notes)?;
+                logInfo!("Release notes published to wiki {}", url);
+            },
+            _ => (),
+        }
+
         Ok(())
     }
 
-    pub async fn clean_pre_releases(&self, git: &Git, api: &GithubApi) -> Result<()> {
+    pub async fn clean_pre_releases(&self, git: &Git, api: &dyn ReleaseProvider, preview: &bool) -> Result<()> {
         logInfo!("Cleaning pre releases");
 
+        // List pre releases that would be deleted
+        if *preview {
+            for tag_info in git.get_tags(&self.tag_prefix)? {
+                if !tag_info.version.pre.is_empty() {
+                    logInfo!("Would delete pre release {}", tag_info.tag());
+                }
+            }
+
+            return Ok(())
+        }
+
         // Clean pre releases first
         api.clean_pre_releases(&self.tag_prefix).await?;
 
@@ -395,11 +1372,56 @@ impl Pkg {
     }
 }
 
+/// Increments the pre release counter of a version when it has the selected pre ID, starts it at 0 otherwise
+fn pre_release_version(ctx: &Ctx, version: Version) -> Result<Version> {
+    let pre_id = ctx.pre_id.as_str();
+    let pre_id_separator = ctx.pre_id_separator.as_str();
+
+    // Pre release counter of the last release if it has the same pre id
+    let counter = version.pre
+        .as_str()
+        .strip_prefix(&format!("{}{}", pre_id, pre_id_separator))
+        .and_then(|n| n.parse::<u32>().ok());
+
+    let pre = match counter {
+        Some(n) => Prerelease::new(format!("{}{}{}", pre_id, pre_id_separator, n + 1).as_str())
+            .context("failed to update pre release")?,
+        None => Prerelease::new(format!("{}{}0", pre_id, pre_id_separator).as_str())
+            .context("failed to update pre release")?,
+    };
+
+    if ctx.explain {
+        match counter {
+            Some(n) => logInfo!("Explain: {} pre release counter incremented from {} to {}", pre_id, n, n + 1),
+            None => logInfo!("Explain: base version has no {} pre release, counter starts at 0", pre_id),
+        }
+    }
+
+    Ok(Version {
+        major: version.major,
+        minor: version.minor,
+        patch: version.patch,
+        pre,
+        build: BuildMetadata::EMPTY,
+    })
+}
+
+/// Release of a package that the packages depending on it are released with
+#[derive(Debug, Clone)]
+pub struct DependencyUpdate {
+    /// Name of the released package
+    pub package: String,
+    /// Released version without the tag prefix
+    pub version: String,
+    /// Names of the package in its npm and cargo manifests
+    pub manifest_names: Vec<String>,
+}
+
 pub type BumpFiles = Vec<BumpFile>;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct BumpFile {
-    /// Version bump file type (cargo, npm, pub, android and ios)
+    /// Version bump file type (cargo, npm, composer, pub, python, helm, android, ios, custom and regex)
     pub target: String,
     /// Path to the file that contains the version
     pub path: String,
@@ -409,6 +1431,41 @@ pub struct BumpFile {
     /// Is this an  individual package that should be published separately
     #[serde(default = "default_package")]
     pub package: bool,
+    /// Regular expression whose first capture group matches the version (custom and regex targets only)
+    #[serde(default)]
+    pub pattern: String,
+    /// Replacement for the whole pattern match, %s is replaced with the version (custom and regex targets only)
+    #[serde(default)]
+    pub template: String,
+    /// If not empty the release date will be written to this key
+    #[serde(default)]
+    pub date_key: String,
+    /// Format of the release date
+    #[serde(default = "default_date_format")]
+    pub date_format: String,
+    /// Also update appVersion (helm target only)
+    #[serde(default)]
+    pub app_version: bool,
+    /// Refresh Cargo.lock after bumping (cargo target only)
+    #[serde(default)]
+    pub update_lock: bool,
+}
+
+impl BumpFile {
+    pub fn new(target: &str, path: &str) -> Self {
+        Self {
+            target: target.to_string(),
+            path: path.to_string(),
+            build_metadata: default_build_metadata(),
+            package: default_package(),
+            pattern: String::new(),
+            template: String::new(),
+            date_key: String::new(),
+            date_format: default_date_format(),
+            app_version: false,
+            update_lock: false,
+        }
+    }
 }
 
 fn default_build_metadata() -> bool {
@@ -418,3 +1475,315 @@ fn default_build_metadata() -> bool {
 fn default_package() -> bool {
     false
 }
+
+fn default_date_format() -> String {
+    "%Y-%m-%d".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ctx::ReleaseType;
+
+    fn release_type(commit_type: &str, bump: &str, section: &str) -> ReleaseType {
+        ReleaseType {
+            commit_type: commit_type.to_string(),
+            bump: bump.to_string(),
+            section: section.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Context of a config with the reserved types
+    fn ctx(config: &str) -> Ctx {
+        let mut ctx: Ctx = serde_yaml::from_str(config).unwrap();
+
+        ctx.types.splice(0..0, [
+            release_type("feat", "minor", "Features"),
+            release_type("fix", "patch", "Bug Fixes"),
+            release_type("revert", "patch", "Reverts"),
+        ]);
+
+        ctx
+    }
+
+    /// Package released as v1.2.3 with these commits (subject, body) since
+    fn pkg(commits: &[(&str, &str)]) -> Pkg {
+        let mut pkg = Pkg::new(String::new(), String::new(), "v".to_string(), vec![]).unwrap();
+
+        pkg.last_release = ReleaseInfo::new("v1.2.3", "v", false);
+        pkg.commits = commits
+            .iter()
+            .enumerate()
+            .map(|(i, (subject, body))| {
+                let hash = format!("{:07x}", i + 1);
+                crate::git::Commit::new(&hash, &hash, subject, body, "Jane Doe")
+            })
+            .collect();
+
+        pkg
+    }
+
+    #[test]
+    fn breaking_change_footer_in_body_bumps_major() {
+        let ctx = ctx("{}");
+        let mut pkg = pkg(&[
+            (
+                "feat: read config from toml",
+                "Configs are read from donder-release.toml.\n\nBREAKING CHANGE: yaml configs are no longer read",
+            ),
+        ]);
+
+        assert!(pkg.load_changelog(&ctx).unwrap());
+        assert_eq!(pkg.changelog.next_release_version, "v2.0.0");
+        assert_eq!(pkg.changelog.commits[0].breaking, "yaml configs are no longer read");
+    }
+
+    #[test]
+    fn breaking_marker_bumps_major() {
+        let ctx = ctx("{}");
+
+        for subject in ["feat!: drop node 14", "fix!: drop node 14", "feat(api)!: drop node 14", "refactor!: drop node 14"] {
+            let mut pkg = pkg(&[(subject, "")]);
+
+            assert!(pkg.load_changelog(&ctx).unwrap(), "{}", subject);
+            assert_eq!(pkg.changelog.next_release_version, "v2.0.0", "{}", subject);
+            assert_eq!(pkg.changelog.commits[0].breaking, "drop node 14", "{}", subject);
+        }
+    }
+
+    #[test]
+    fn breaking_change_below_min_commits_is_released_when_enabled() {
+        let commits = [("feat!: drop node 14", "")];
+
+        let mut batched = pkg(&commits);
+        assert!(!batched.load_changelog(&ctx("min_commits: 3")).unwrap());
+
+        let mut released = pkg(&commits);
+        assert!(released.load_changelog(&ctx("min_commits: { count: 3, always_release_breaking: true }")).unwrap());
+        assert_eq!(released.changelog.next_release_version, "v2.0.0");
+    }
+
+    #[test]
+    fn commits_marked_to_skip_release_are_ignored() {
+        let mut ctx = ctx("{}");
+        ctx.strict = true;
+
+        let mut pkg = pkg(&[
+            ("feat: add a flag [skip release]", ""),
+            ("fix: handle empty configs", "Not released yet.\n\n[skip release]"),
+            ("WIP [skip release]", ""),
+            ("fix: read the config path", ""),
+        ]);
+
+        assert!(pkg.load_changelog(&ctx).unwrap());
+        assert_eq!(pkg.changelog.next_release_version, "v1.2.4");
+        assert_eq!(pkg.changelog.commits.len(), 1);
+        assert_eq!(pkg.changelog.commits[0].desc, "read the config path");
+
+        let last_release = pkg.last_release.tag();
+        pkg.changelog.write_notes(&last_release, &ctx.types, "https://github.com/o/r", &ctx.notes, &false).unwrap();
+
+        assert!(pkg.changelog.notes.contains("read the config path"));
+        assert!(!pkg.changelog.notes.contains("add a flag"));
+        assert!(!pkg.changelog.notes.contains("handle empty configs"));
+    }
+
+    #[test]
+    fn commits_without_description_are_kept_when_allowed() {
+        let commits = [("fix:", "handle empty configs"), ("feat(cli):", "")];
+
+        let mut skipped = pkg(&commits);
+        assert!(!skipped.load_changelog(&ctx("{}")).unwrap());
+
+        let mut kept = pkg(&commits);
+        assert!(kept.load_changelog(&ctx("allow_empty_description: true")).unwrap());
+        assert_eq!(kept.changelog.next_release_version, "v1.3.0");
+        assert_eq!(kept.changelog.commits[0].desc, "handle empty configs");
+        assert_eq!(kept.changelog.commits[1].desc, "(no description)");
+        assert_eq!(kept.changelog.commits[1].scope, "cli");
+    }
+
+    #[test]
+    fn commits_of_excluded_authors_are_ignored() {
+        let ctx = ctx("exclude_authors: [\"dependabot[bot]\"]");
+        let mut pkg = pkg(&[("feat: bump serde to 1.0.200", ""), ("fix: handle empty configs", "")]);
+        pkg.commits[0].author = "dependabot[bot]".to_string();
+
+        assert!(pkg.load_changelog(&ctx).unwrap());
+        assert_eq!(pkg.changelog.next_release_version, "v1.2.4");
+        assert_eq!(pkg.changelog.commits.len(), 1);
+        assert_eq!(pkg.changelog.commits[0].desc, "handle empty configs");
+    }
+
+    #[test]
+    fn pre_release_counter_uses_the_separator() {
+        for (separator, last, next) in [(".", "1.3.0-beta.2", "1.3.0-beta.3"), ("", "1.3.0-beta2", "1.3.0-beta3")] {
+            let mut ctx = ctx(&format!("pre_id_separator: \"{}\"", separator));
+            ctx.pre_id = "beta".to_string();
+
+            let version = pre_release_version(&ctx, Version::parse(last).unwrap()).unwrap();
+            assert_eq!(version.to_string(), next, "{:?}", separator);
+
+            let version = pre_release_version(&ctx, Version::parse("1.3.0").unwrap()).unwrap();
+            assert_eq!(version.to_string(), format!("1.3.0-beta{}0", separator), "{:?}", separator);
+        }
+    }
+
+    #[test]
+    fn long_descriptions_are_truncated_by_characters() {
+        let ctx = ctx("max_desc_length: 10");
+        let mut pkg = pkg(&[("feat: añadir soporte para configuración 🎉", ""), ("fix: corregir", "")]);
+
+        assert!(pkg.load_changelog(&ctx).unwrap());
+
+        let last_release = pkg.last_release.tag();
+        pkg.changelog.write_notes(&last_release, &ctx.types, "https://github.com/o/r", &ctx.notes, &false).unwrap();
+
+        assert!(pkg.changelog.notes.contains("añadir sop…"));
+        assert!(!pkg.changelog.notes.contains("añadir soporte"));
+        assert!(pkg.changelog.notes.contains("corregir"));
+        assert!(!pkg.changelog.notes.contains("corregir…"));
+    }
+
+    #[test]
+    fn section_counts_match_the_commits_listed() {
+        let ctx = ctx("section_counts: true");
+        let mut pkg = pkg(&[
+            ("feat(api): add endpoint", ""),
+            ("feat(api): add pagination", ""),
+            ("feat: read config from toml", ""),
+            ("fix: handle empty configs", ""),
+        ]);
+
+        assert!(pkg.load_changelog(&ctx).unwrap());
+
+        let last_release = pkg.last_release.tag();
+        pkg.changelog.write_notes(&last_release, &ctx.types, "https://github.com/o/r", &ctx.notes, &false).unwrap();
+
+        let sections: Vec<&str> = pkg.changelog.notes.split("\r\n### ").skip(1).collect();
+        assert_eq!(sections.len(), 2);
+
+        for section in sections {
+            let (title, body) = section.split_once("\r\n").unwrap();
+            let count = body.lines().filter(|line| line.contains("/commit/")).count();
+
+            assert!(title.ends_with(&format!("({})", count)), "{}", title);
+        }
+
+        assert!(pkg.changelog.notes.contains("### Features (3)"));
+        assert!(pkg.changelog.notes.contains("### Bug Fixes (1)"));
+    }
+
+    #[test]
+    fn manually_edited_changelog_header_is_kept() {
+        let dir = std::env::temp_dir().join(format!("donder-release-changelog-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let changelog_file = dir.join("CHANGELOG.md").to_string_lossy().to_string();
+        fs::write(&changelog_file, "# Changelog\r\n\r\nReleases of the api.\r\n\r\n## v1.2.3\r\n\r\n- old release\r\n").unwrap();
+
+        pkg(&[]).write_changelog_file(&changelog_file, "## v1.2.4\r\n\r\n- new release\r\n").unwrap();
+
+        let contents = fs::read_to_string(&changelog_file).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            contents,
+            "# Changelog\r\n\r\nReleases of the api.\r\n\r\n## v1.2.4\r\n\r\n- new release\r\n\r\n## v1.2.3\r\n\r\n- old release\r\n",
+        );
+    }
+
+    #[test]
+    fn reverted_feature_only_bumps_patch() {
+        let commits = [
+            ("feat: add endpoint", ""),
+            ("revert: feat: add endpoint", "This reverts commit 0000001f3a9c2e7b1d4c5a6b7e8f9a0b1c2d3e4f."),
+        ];
+
+        let mut counted = pkg(&commits);
+        assert!(counted.load_changelog(&ctx("{}")).unwrap());
+        assert_eq!(counted.changelog.next_release_version, "v1.3.0");
+
+        let mut reverted = pkg(&commits);
+        assert!(reverted.load_changelog(&ctx("reverts_affect_bump: true")).unwrap());
+        assert_eq!(reverted.changelog.next_release_version, "v1.2.4");
+    }
+
+    #[test]
+    fn notes_use_the_configured_bullets_and_indent() {
+        let ctx = ctx("bullet_char: \"*\"\nnested_indent: 4");
+        let mut pkg = pkg(&[("feat(api): add endpoint", ""), ("feat: read config from toml", "")]);
+
+        assert!(pkg.load_changelog(&ctx).unwrap());
+
+        let last_release = pkg.last_release.tag();
+        pkg.changelog.write_notes(&last_release, &ctx.types, "https://github.com/o/r", &ctx.notes, &false).unwrap();
+
+        let notes = &pkg.changelog.notes;
+        assert!(notes.contains("\r\n* **api:**\r\n"), "{}", notes);
+        assert!(notes.contains("\r\n    * add endpoint ("), "{}", notes);
+        assert!(notes.contains("\r\n* read config from toml ("), "{}", notes);
+        assert!(!notes.contains("\r\n- "), "{}", notes);
+    }
+
+    #[test]
+    fn only_hidden_commits_release_a_patch_when_enabled() {
+        let types = "types: [{ commit_type: docs, section: Documentation }]";
+        let commits = [("docs: document the config file", ""), ("chore: update ci", "")];
+
+        let mut skipped = pkg(&commits);
+        assert!(!skipped.load_changelog(&ctx(types)).unwrap());
+
+        let mut released = pkg(&commits);
+        assert!(released.load_changelog(&ctx(&format!("{}\npatch_on_hidden_only: true", types))).unwrap());
+        assert_eq!(released.changelog.next_release_version, "v1.2.4");
+        assert_eq!(released.changelog.commits.len(), 1);
+        assert_eq!(released.changelog.commits[0].section_type, "docs");
+    }
+
+    #[test]
+    fn releases_above_max_version_fail() {
+        let mut under = pkg(&[("feat: add endpoint", "")]);
+        assert!(under.load_changelog(&ctx("max_version: 1.99.99")).unwrap());
+        assert_eq!(under.changelog.next_release_version, "v1.3.0");
+
+        let mut over = pkg(&[("feat!: drop node 14", "")]);
+        let error = over.load_changelog(&ctx("max_version: 1.99.99")).unwrap_err();
+        assert!(error.to_string().contains("exceeds max_version 1.99.99"), "{}", error);
+
+        let mut allowed = pkg(&[("feat!: drop node 14", "")]);
+        let mut ctx = ctx("max_version: 1.99.99");
+        ctx.allow_exceed_ceiling = true;
+        assert!(allowed.load_changelog(&ctx).unwrap());
+        assert_eq!(allowed.changelog.next_release_version, "v2.0.0");
+    }
+
+    #[test]
+    fn manifest_version_without_tag_uses_the_whole_history() {
+        let ctx = ctx("{}");
+        let mut pkg = pkg(&[]);
+        pkg.manifest_version = Some(Version::parse("41.0.0").unwrap());
+
+        pkg.last_release(&ctx).unwrap();
+
+        assert_eq!(pkg.last_release.tag(), "v41.0.0");
+        assert!(!pkg.last_release.initial);
+        assert_eq!(pkg.revision_range(&ctx).unwrap(), "");
+
+        pkg.last_release.update_head("0000001");
+        assert_eq!(pkg.revision_range(&ctx).unwrap(), "0000001..HEAD");
+    }
+
+    #[test]
+    fn commits_of_excluded_authors_can_still_count_for_the_bump() {
+        let ctx = ctx("exclude_authors: [\"dependabot[bot]\"]\nexclude_authors_from_bump: false");
+        let mut pkg = pkg(&[("feat: bump serde to 1.0.200", ""), ("fix: handle empty configs", "")]);
+        pkg.commits[0].author = "dependabot[bot]".to_string();
+
+        assert!(pkg.load_changelog(&ctx).unwrap());
+        assert_eq!(pkg.changelog.next_release_version, "v1.3.0");
+        assert_eq!(pkg.changelog.commits.len(), 1);
+        assert_eq!(pkg.changelog.commits[0].desc, "handle empty configs");
+    }
+}